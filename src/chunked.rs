@@ -0,0 +1,46 @@
+use std::io::{self, Write};
+
+// parse the size line of a chunk (RFC 9112 section 7.1), ignoring chunk extensions
+pub fn parse_chunk_size(line: &str) -> Option<usize> {
+    let size = line.split(';').next().unwrap_or("").trim_end_matches([' ', '\t']);
+    if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    usize::from_str_radix(size, 16).ok()
+}
+
+// writes a response body using the chunked transfer coding,
+// each call to `write` sends one chunk to the client.
+// `finish` must be called to send the last chunk, a writer dropped before that
+// leaves the body incomplete so the client can see that the response was cut
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ChunkedWriter { inner }
+    }
+    // send the last chunk, ending the body
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(b"0\r\n\r\n")?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // an empty chunk would be read as the end of the body
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.inner.write_all(format!("{:X}\r\n", buf.len()).as_bytes())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,395 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use crate::database::PoolSettings;
+use crate::logger::LogLevel;
+use crate::request::{RequestLimits, RequestTimeouts};
+use crate::static_files::SymlinkPolicy;
+
+// file read when no other one is set with --config or SERVER_CONFIG
+const DEFAULT_CONFIG_FILE: &str = "server.toml";
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+// the timeouts are added to the current instant, larger values could overflow it
+const MAX_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+// settings read from the config file, the environment (SERVER_<NAME>) and the command line
+// (--name), in this order of precedence from the lowest to the highest
+const SETTINGS: [(&str, &str); 22] = [
+    ("address", "address where the server listens"),
+    ("port", "port where the server listens"),
+    ("document_root", "folder with the static files"),
+    ("static_prefix", "path where the static files are served"),
+    ("symlink_policy", "links in the document root that are followed: follow, within_root or deny"),
+    ("database", "path of the sqlite database"),
+    ("database_pool_size", "connections to the database open at the same time"),
+    ("database_busy_timeout", "seconds a query waits for a database locked by another connection"),
+    ("database_acquire_timeout", "seconds a request waits for a free database connection"),
+    ("workers", "threads that serve the connections"),
+    ("queue_size", "connections that can wait for a free worker"),
+    ("queue_full_policy", "what to do with new connections when the server is full: reject (503) or block"),
+    ("retry_after", "seconds sent in the Retry-After header of the rejected connections"),
+    ("max_connections", "connections open at the same time"),
+    ("idle_timeout", "seconds a connection waits for a new request"),
+    ("read_timeout", "seconds each read of a request can wait for the client"),
+    ("header_timeout", "seconds the client has to send the headers of a request"),
+    ("body_timeout", "seconds the client has to send the body of a request"),
+    ("write_timeout", "seconds each write of a response can wait for the client"),
+    ("shutdown_timeout", "seconds the requests have to finish when the server stops"),
+    ("max_requests", "requests served in a single connection"),
+    ("log_level", "off, error, warn, info or debug"),
+];
+
+pub struct ServerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    // connections served or waiting in the queue, new connections beyond it follow the queue full policy
+    pub max_connections: usize,
+    // time a connection can stay without sending a new request and to send each part of a request
+    pub request_timeouts: RequestTimeouts,
+    // time a write of the response waits for the client to read, a slow client is disconnected
+    pub write_timeout: Duration,
+    // requests served in a single connection before closing it
+    pub max_requests_per_connection: usize,
+    pub request_limits: RequestLimits,
+    // folder with the static files and the path where they are served
+    pub document_root: PathBuf,
+    pub static_prefix: String,
+    pub symlink_policy: SymlinkPolicy,
+    pub database_path: PathBuf,
+    pub database_pool: PoolSettings,
+    // threads of the pool that serve the connections and connections that can wait for one
+    pub workers: usize,
+    pub queue_size: usize,
+    pub queue_full_policy: QueueFullPolicy,
+    // time the requests in progress have to finish when the server is stopped
+    pub shutdown_timeout: Duration,
+    pub log_level: LogLevel,
+}
+
+// what to do with a new connection when the queue of the pool or the connection limit is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    // answer 503 with the Retry-After header and close the connection
+    Reject { retry_after: Duration },
+    // stop accepting connections until the queue has space
+    Block,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    // the config file couldn't be read or isn't valid toml
+    File(PathBuf, String),
+    UnknownSetting { name: String, origin: String },
+    InvalidValue { name: String, value: String, origin: String, reason: String },
+    // the settings have the right types but invalid values, like a document root that doesn't exist
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File(path, err) => write!(f, "an error ocurred to read the config file {}: {}", path.display(), err),
+            ConfigError::UnknownSetting { name, origin } => write!(f, "unknown setting {} in {}", name, origin),
+            ConfigError::InvalidValue { name, value, origin, reason } => {
+                write!(f, "invalid value {:?} for {} in {}: {}", value, name, origin, reason)
+            },
+            ConfigError::Invalid(errors) => write!(f, "invalid configuration:\n  {}", errors.join("\n  ")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8000,
+            max_connections: 1024,
+            request_timeouts: RequestTimeouts::default(),
+            write_timeout: Duration::from_secs(30),
+            max_requests_per_connection: 100,
+            request_limits: RequestLimits::default(),
+            document_root: PathBuf::from("src/public"),
+            static_prefix: "/".to_string(),
+            symlink_policy: SymlinkPolicy::FollowWithinRoot,
+            database_path: PathBuf::from("peoples.db"),
+            database_pool: PoolSettings::default(),
+            workers: 16,
+            queue_size: 64,
+            queue_full_policy: QueueFullPolicy::Reject { retry_after: DEFAULT_RETRY_AFTER },
+            shutdown_timeout: Duration::from_secs(10),
+            log_level: LogLevel::Info,
+        }
+    }
+}
+
+impl ServerConfig {
+    // configuration of the command line arguments (without the program name) and the environment
+    pub fn load(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        Self::load_from(args, |name| env::var(name).ok())
+    }
+
+    // same as `load`, reading the environment variables with `var`
+    pub fn load_from(args: impl IntoIterator<Item = String>, var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut flags = parse_args(args)?;
+        let mut config = ServerConfig::default();
+
+        // the file set in the command line wins over the one in the environment
+        let config_file = match flags.iter().rposition(|(name, _)| name == "config") {
+            Some(i) => Some(PathBuf::from(flags.remove(i).1)),
+            None => var("SERVER_CONFIG").map(PathBuf::from),
+        };
+        flags.retain(|(name, _)| name != "config");
+        match config_file {
+            Some(path) => config.apply_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => config.apply_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => {},
+        }
+        for (name, _) in SETTINGS {
+            let var_name = format!("SERVER_{}", name.to_ascii_uppercase());
+            if let Some(value) = var(&var_name) {
+                config.set(name, &value, &format!("the environment variable {}", var_name))?;
+            }
+        }
+        for (name, value) in flags {
+            config.set(&name, &value, "the command line")?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let file_error = |err: String| ConfigError::File(path.to_path_buf(), err);
+        let content = fs::read_to_string(path).map_err(|err| file_error(err.to_string()))?;
+        let table: toml::Table = content.parse().map_err(|err: toml::de::Error| file_error(err.to_string()))?;
+        let origin = format!("the config file {}", path.display());
+        for (name, value) in table {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                value => return Err(ConfigError::InvalidValue {
+                    name, value: value.to_string(), origin, reason: "expected a string or a number".to_string(),
+                }),
+            };
+            self.set(&name, &value, &origin)?;
+        }
+        Ok(())
+    }
+
+    // set a setting from its text value, `origin` describes where it was set
+    fn set(&mut self, name: &str, value: &str, origin: &str) -> Result<(), ConfigError> {
+        let invalid = |reason: String| ConfigError::InvalidValue {
+            name: name.to_string(), value: value.to_string(), origin: origin.to_string(), reason,
+        };
+        match name {
+            "address" => self.address = parse(value).map_err(invalid)?,
+            "port" => self.port = parse(value).map_err(invalid)?,
+            "document_root" => self.document_root = PathBuf::from(value),
+            "static_prefix" => self.static_prefix = value.to_string(),
+            "symlink_policy" => self.symlink_policy = parse(value).map_err(invalid)?,
+            "database" => self.database_path = PathBuf::from(value),
+            "database_pool_size" => self.database_pool.size = parse(value).map_err(invalid)?,
+            "database_busy_timeout" => self.database_pool.busy_timeout = parse_seconds(value).map_err(invalid)?,
+            "database_acquire_timeout" => self.database_pool.acquire_timeout = parse_seconds(value).map_err(invalid)?,
+            "workers" => self.workers = parse(value).map_err(invalid)?,
+            "queue_size" => self.queue_size = parse(value).map_err(invalid)?,
+            "queue_full_policy" => self.queue_full_policy = match value.trim().to_ascii_lowercase().as_str() {
+                // a retry_after set before is kept
+                "reject" => QueueFullPolicy::Reject { retry_after: self.retry_after() },
+                "block" => QueueFullPolicy::Block,
+                _ => return Err(invalid("expected reject or block".to_string())),
+            },
+            "retry_after" => {
+                let retry_after = parse_seconds(value).map_err(invalid)?;
+                match &mut self.queue_full_policy {
+                    QueueFullPolicy::Reject { retry_after: current } => *current = retry_after,
+                    QueueFullPolicy::Block => return Err(invalid("only used with queue_full_policy = reject".to_string())),
+                }
+            },
+            "max_connections" => self.max_connections = parse(value).map_err(invalid)?,
+            "idle_timeout" => self.request_timeouts.idle = parse_seconds(value).map_err(invalid)?,
+            "read_timeout" => self.request_timeouts.read = parse_seconds(value).map_err(invalid)?,
+            "header_timeout" => self.request_timeouts.header = parse_seconds(value).map_err(invalid)?,
+            "body_timeout" => self.request_timeouts.body = parse_seconds(value).map_err(invalid)?,
+            "write_timeout" => self.write_timeout = parse_seconds(value).map_err(invalid)?,
+            "shutdown_timeout" => self.shutdown_timeout = parse_seconds(value).map_err(invalid)?,
+            "max_requests" => self.max_requests_per_connection = parse(value).map_err(invalid)?,
+            "log_level" => self.log_level = parse(value).map_err(invalid)?,
+            _ => return Err(ConfigError::UnknownSetting { name: name.to_string(), origin: origin.to_string() }),
+        }
+        Ok(())
+    }
+
+    fn retry_after(&self) -> Duration {
+        match self.queue_full_policy {
+            QueueFullPolicy::Reject { retry_after } => retry_after,
+            QueueFullPolicy::Block => DEFAULT_RETRY_AFTER,
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+        if self.workers == 0 {
+            errors.push("workers must be at least 1".to_string());
+        }
+        if self.queue_size == 0 {
+            errors.push("queue_size must be at least 1".to_string());
+        }
+        if self.max_requests_per_connection == 0 {
+            errors.push("max_requests must be at least 1".to_string());
+        }
+        if self.max_connections == 0 {
+            errors.push("max_connections must be at least 1".to_string());
+        }
+        if self.database_pool.size == 0 {
+            errors.push("database_pool_size must be at least 1".to_string());
+        }
+        let timeouts = [
+            ("idle_timeout", self.request_timeouts.idle),
+            ("read_timeout", self.request_timeouts.read),
+            ("header_timeout", self.request_timeouts.header),
+            ("body_timeout", self.request_timeouts.body),
+            ("write_timeout", self.write_timeout),
+            ("database_acquire_timeout", self.database_pool.acquire_timeout),
+        ];
+        for (name, timeout) in timeouts {
+            if timeout.is_zero() {
+                errors.push(format!("{} must be greater than 0", name));
+            } else if timeout > MAX_TIMEOUT {
+                errors.push(format!("{} must be at most {} seconds", name, MAX_TIMEOUT.as_secs()));
+            }
+        }
+        // these can be 0, like a shutdown that doesn't wait for the requests
+        let waits = [
+            ("shutdown_timeout", self.shutdown_timeout),
+            ("retry_after", self.retry_after()),
+            ("database_busy_timeout", self.database_pool.busy_timeout),
+        ];
+        for (name, _) in waits.iter().filter(|(_, wait)| *wait > MAX_TIMEOUT) {
+            errors.push(format!("{} must be at most {} seconds", name, MAX_TIMEOUT.as_secs()));
+        }
+        if !self.document_root.is_dir() {
+            errors.push(format!("the document root {} isn't a folder", self.document_root.display()));
+        }
+        if !self.static_prefix.starts_with('/') {
+            errors.push(format!("the static prefix {} must start with /", self.static_prefix));
+        }
+        if let Some(parent) = self.database_path.parent().filter(|p| !p.as_os_str().is_empty() && !p.is_dir()) {
+            errors.push(format!("the folder of the database {} doesn't exist", parent.display()));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(errors)) }
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, String>
+where T::Err: fmt::Display {
+    value.trim().parse().map_err(|err: T::Err| err.to_string())
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse(value)?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| "expected a positive number of seconds".to_string())
+}
+
+// flags like `--port 8000` or `--port=8000`, with the names of the settings in kebab case
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut flags = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            return Err(ConfigError::UnknownSetting { name: arg, origin: "the command line".to_string() });
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (flag.to_string(), args.next()),
+        };
+        let Some(value) = value else {
+            return Err(ConfigError::InvalidValue {
+                name, value: String::new(), origin: "the command line".to_string(), reason: "missing value".to_string(),
+            });
+        };
+        flags.push((name.replace('-', "_"), value));
+    }
+    Ok(flags)
+}
+
+// help of the command line, listing the settings
+pub fn usage() -> String {
+    let mut usage = String::from("usage: http-server [--config <file>] [--<setting> <value>]...\n\nsettings:\n");
+    for (name, description) in SETTINGS {
+        usage.push_str(&format!("  --{:<26} {}\n", name.replace('_', "-"), description));
+    }
+    usage.push_str(&format!(
+        "\nthe settings can also be set in a toml file ({} by default) or in environment variables like SERVER_PORT\n",
+        DEFAULT_CONFIG_FILE,
+    ));
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(args: &[&str], vars: &[(&str, &str)]) -> Result<ServerConfig, ConfigError> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        ServerConfig::load_from(args.iter().map(|a| a.to_string()), |name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn command_line_wins_over_environment_and_file() {
+        let dir = env::temp_dir().join(format!("http-server-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("server.toml");
+        fs::write(&file, "port = 7000\nworkers = 2\nidle_timeout = 1.5\nlog_level = \"debug\"\n").unwrap();
+        let file = file.to_str().unwrap();
+
+        let config = load(&["--config", file, "--port=9000"], &[("SERVER_PORT", "8080"), ("SERVER_WORKERS", "4")]).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.workers, 4);
+        assert_eq!(config.request_timeouts.idle, Duration::from_millis(1500));
+        assert_eq!(config.log_level, LogLevel::Debug);
+
+        let config = load(&["--document-root", dir.to_str().unwrap()], &[("SERVER_CONFIG", file)]).unwrap();
+        assert_eq!(config.port, 7000);
+        assert_eq!(config.document_root, dir);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn policies_are_read_from_the_settings() {
+        let config = load(&["--symlink-policy", "deny", "--retry-after", "3"], &[]).unwrap();
+        assert_eq!(config.symlink_policy, SymlinkPolicy::Deny);
+        assert_eq!(config.queue_full_policy, QueueFullPolicy::Reject { retry_after: Duration::from_secs(3) });
+        let config = load(&["--queue-full-policy", "reject"], &[("SERVER_RETRY_AFTER", "2")]).unwrap();
+        assert_eq!(config.queue_full_policy, QueueFullPolicy::Reject { retry_after: Duration::from_secs(2) });
+        let config = load(&[], &[("SERVER_QUEUE_FULL_POLICY", "block")]).unwrap();
+        assert_eq!(config.queue_full_policy, QueueFullPolicy::Block);
+
+        assert!(matches!(load(&["--symlink-policy", "sometimes"], &[]), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(load(&["--queue-full-policy", "block", "--retry-after", "2"], &[]), Err(ConfigError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn invalid_settings_are_reported() {
+        assert!(matches!(load(&["--port", "http"], &[]), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(load(&["--colour", "red"], &[]), Err(ConfigError::UnknownSetting { .. })));
+        assert!(matches!(load(&["--port"], &[]), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(load(&[], &[("SERVER_LOG_LEVEL", "loud")]), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(load(&["--config", "missing.toml"], &[]), Err(ConfigError::File(..))));
+        match load(&["--workers", "0", "--document-root", "missing-folder"], &[]) {
+            Err(ConfigError::Invalid(errors)) => assert_eq!(errors.len(), 2),
+            _ => panic!("expected the validation errors"),
+        }
+        match load(&["--header-timeout", "1.8e19", "--body-timeout", "86401", "--shutdown-timeout", "1e19"], &[]) {
+            Err(ConfigError::Invalid(errors)) => assert_eq!(errors.len(), 3),
+            _ => panic!("expected the validation errors"),
+        }
+    }
+}
@@ -1,18 +1,203 @@
-use rusqlite::{Connection, Result};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::Duration;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row, ToSql, Transaction, TransactionBehavior};
+use rusqlite::types::Value;
 use serde::{Serialize, Deserialize};
+use crate::error::ServerError;
 
-#[derive(Serialize, Deserialize)]
-pub struct DebugPeople {
-    id: i64,
-    name: String,
-    age: i64,
+// connections shared by the handlers, opened at startup by `init`
+static POOL: OnceLock<ConnectionPool> = OnceLock::new();
+
+// statements kept prepared in each connection, the queries of the pages
+// change with the filters and the sort so there are more than the fixed ones
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSettings {
+    // connections open at the same time
+    pub size: usize,
+    // time a query waits for the lock of another connection before failing with SQLITE_BUSY
+    pub busy_timeout: Duration,
+    // time a request waits for a free connection before answering 503
+    pub acquire_timeout: Duration,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        PoolSettings {
+            size: 8,
+            busy_timeout: Duration::from_secs(5),
+            acquire_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+// fixed number of connections to the same database, a connection is taken
+// by `get` and given back when the `PooledConnection` is dropped
+pub struct ConnectionPool {
+    state: Mutex<PoolState>,
+    available: Condvar,
+    path: PathBuf,
+    settings: PoolSettings,
+}
+
+struct PoolState {
+    idle: Vec<Connection>,
+    // connections that were closed after an error, they are opened again by `get`
+    missing: usize,
+}
+
+pub struct PooledConnection<'a> {
+    conn: Option<Connection>,
+    pool: &'a ConnectionPool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct People {
+    pub id: i64,
+    pub name: String,
+    pub age: i64,
+}
+
+// fields of people that can be used to sort a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeopleField {
+    Id,
+    Name,
+    Age,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    pub field: PeopleField,
+    pub descending: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PeopleFilter {
+    pub name_contains: Option<String>,
+    pub min_age: Option<i64>,
+    pub max_age: Option<i64>,
+}
+
+// where a page starts: skipping rows, or right after or before the row with
+// the values of the sort keys in a cursor
+#[derive(Debug)]
+pub enum PageStart {
+    Offset(usize),
+    After(Vec<Value>),
+    Before(Vec<Value>),
+}
+
+#[derive(Debug)]
+pub struct PeopleQuery {
+    pub filter: PeopleFilter,
+    // the id is always used as the last key, so the order is the same in every page
+    pub sort: Vec<SortKey>,
+    pub start: PageStart,
+    pub limit: usize,
+}
+
+// open the connections and create the tables, called once at startup
+pub fn init(path: &Path, settings: &PoolSettings) -> Result<()> {
+    let pool = ConnectionPool::open(path, settings)?;
+    let _ = POOL.set(pool);
+    Ok(())
+}
+
+// connection of the pool, 503 when all the connections are in use for too long
+pub fn connect_db() -> Result<PooledConnection<'static>, ServerError> {
+    POOL.get()
+        .ok_or_else(|| ServerError::Internal("the database isn't initialized".to_string()))?
+        .get()
+}
+
+impl ConnectionPool {
+    pub fn open(path: &Path, settings: &PoolSettings) -> Result<Self> {
+        let idle = (0..settings.size.max(1))
+            .map(|_| open_connection(path, settings.busy_timeout))
+            .collect::<Result<Vec<Connection>>>()?;
+        create_db(&idle[0])?;
+        Ok(ConnectionPool {
+            state: Mutex::new(PoolState { idle, missing: 0 }),
+            available: Condvar::new(),
+            path: path.to_path_buf(),
+            settings: *settings,
+        })
+    }
+
+    pub fn get(&self) -> Result<PooledConnection<'_>, ServerError> {
+        let state = self.lock();
+        let (mut state, _) = self.available
+            .wait_timeout_while(state, self.settings.acquire_timeout, |state| state.idle.is_empty() && state.missing == 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(conn) = state.idle.pop() {
+            return Ok(PooledConnection { conn: Some(conn), pool: self });
+        }
+        if state.missing == 0 {
+            return Err(ServerError::Unavailable("all the database connections are in use, try again later".to_string()));
+        }
+        // the connection is opened without the lock, the other requests keep using the pool
+        state.missing -= 1;
+        drop(state);
+        match open_connection(&self.path, self.settings.busy_timeout) {
+            Ok(conn) => Ok(PooledConnection { conn: Some(conn), pool: self }),
+            Err(err) => {
+                self.discard();
+                Err(err.into())
+            },
+        }
+    }
+
+    // a connection that was closed, another one is opened in its place by the next `get`
+    fn discard(&self) {
+        self.lock().missing += 1;
+        self.available.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
-pub fn connect_db() -> Result<Connection> {
-    let conn = Connection::open("peoples.db")?;
-    create_db(&conn)?;
+// WAL lets the readers work while a connection writes, the busy timeout makes
+// the writers wait for each other instead of failing
+fn open_connection(path: &Path, busy_timeout: Duration) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(busy_timeout)?;
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        log_warn!("the database {} doesn't support WAL, it uses the journal mode {}", path.display(), journal_mode);
+    }
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
     Ok(conn)
 }
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("the connection is only taken when it is dropped")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else { return };
+        // a handler that failed or panicked in a transaction doesn't leave it open for the next one
+        if !conn.is_autocommit() && let Err(err) = conn.execute_batch("ROLLBACK") {
+            log_error!("an error ocurred to rollback a transaction, the connection is replaced!\n{}", err);
+            drop(conn);
+            self.pool.discard();
+            return;
+        }
+        self.pool.lock().idle.push(conn);
+        self.pool.available.notify_one();
+    }
+}
+
 fn create_db(conn: &Connection) -> Result<()> {
     conn.execute(
       "CREATE TABLE IF NOT EXISTS people (
@@ -23,28 +208,192 @@ fn create_db(conn: &Connection) -> Result<()> {
     [],)?;
     Ok(())
 }
-pub fn insert_new_people(conn: &Connection, name: &str, age: &str) -> Result<()> {
-    conn.execute("
+fn people_from_row(row: &Row) -> Result<People> {
+    Ok(People { id: row.get(0)?, name: row.get(1)?, age: row.get(2)? })
+}
+
+// insert the people and return its id, `None` when the name is already used
+pub fn insert_new_people(conn: &Connection, name: &str, age: i64) -> Result<Option<i64>> {
+    let inserted = conn.prepare_cached("
       INSERT INTO People (name, age) VALUES (?1, ?2) ON CONFLICT(name) DO NOTHING
-    ", [name, age],)?;
-    Ok(())
+    ")?.execute(params![name, age])?;
+    Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+}
+// replace the name and age of the people, returns false when the id doesn't exist
+pub fn update_people(conn: &Connection, id: i64, name: &str, age: i64) -> Result<bool> {
+    let updated = conn.prepare_cached("
+      UPDATE People SET name = ?2, age = ?3 WHERE id = ?1
+    ")?.execute(params![id, name, age])?;
+    Ok(updated > 0)
 }
-pub fn delete_people(conn: &Connection, id: i64) -> Result<()> {
-    conn.execute("
+// returns false when the id doesn't exist
+pub fn delete_people(conn: &Connection, id: i64) -> Result<bool> {
+    let deleted = conn.prepare_cached("
       DELETE FROM People WHERE id = ?1
-    ", [id,],)?;
-    Ok(())
+    ")?.execute([id])?;
+    Ok(deleted > 0)
+}
+pub fn fetch_people_by_id(conn: &Connection, id: i64) -> Result<Option<People>> {
+    conn.prepare_cached("SELECT id, name, age FROM people WHERE id = ?1")?.query_row([id], people_from_row).optional()
+}
+pub fn fetch_people_by_name(conn: &Connection, name: &str) -> Result<Option<People>> {
+    conn.prepare_cached("SELECT id, name, age FROM people WHERE name = ?1")?.query_row([name], people_from_row).optional()
+}
+// create the people with the name or update its age, returns true when it was created.
+// the transaction takes the write lock before reading, so two upserts of a new name don't both insert it
+pub fn upsert_people(conn: &Connection, name: &str, age: i64) -> Result<(People, bool)> {
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let result = match fetch_people_by_name(&tx, name)? {
+        Some(people) => {
+            update_people(&tx, people.id, name, age)?;
+            (People { age, ..people }, false)
+        },
+        None => {
+            tx.prepare_cached("INSERT INTO People (name, age) VALUES (?1, ?2)")?.execute(params![name, age])?;
+            (People { id: tx.last_insert_rowid(), name: name.to_string(), age }, true)
+        },
+    };
+    tx.commit()?;
+    Ok(result)
+}
+pub fn count_people(conn: &Connection, filter: &PeopleFilter) -> Result<i64> {
+    let (conditions, params) = filter_conditions(filter);
+    let sql = format!("SELECT COUNT(*) FROM people{}", where_clause(&conditions));
+    let params: Vec<&dyn ToSql> = params.iter().map(|p| p as &dyn ToSql).collect();
+    conn.prepare_cached(&sql)?.query_row(params.as_slice(), |row| row.get(0))
+}
+// rows of a page in the order of the sort keys, a `Before` page is read
+// backwards from its cursor and reversed
+pub fn fetch_people_page(conn: &Connection, query: &PeopleQuery) -> Result<Vec<People>> {
+    let (mut conditions, mut params) = filter_conditions(&query.filter);
+    let backwards = matches!(query.start, PageStart::Before(_));
+    let keys: Vec<(PeopleField, bool)> = query.sort.iter()
+        .map(|key| (key.field, key.descending != backwards))
+        .collect();
+    if let PageStart::After(values) | PageStart::Before(values) = &query.start {
+        conditions.push(keyset_condition(&keys, values.len(), params.len()));
+        params.extend(values.iter().cloned());
+    }
+    let order = keys.iter()
+        .map(|(field, descending)| format!("{} {}", field.column(), if *descending { "DESC" } else { "ASC" }))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let offset = match query.start {
+        PageStart::Offset(offset) => offset,
+        _ => 0,
+    };
+    let sql = format!(
+        "SELECT id, name, age FROM people{} ORDER BY {} LIMIT {} OFFSET {}",
+        where_clause(&conditions), order, query.limit, offset,
+    );
+    let params: Vec<&dyn ToSql> = params.iter().map(|p| p as &dyn ToSql).collect();
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let mut page = stmt.query_map(params.as_slice(), people_from_row)?.collect::<Result<Vec<People>>>()?;
+    if backwards {
+        page.reverse();
+    }
+    Ok(page)
 }
-pub fn fetch_people(conn: &Connection) -> Result<Vec<DebugPeople>>{
-    let mut stmt = conn.prepare("SELECT id, name, age FROM people")?;
-    let rows = stmt.query_map([], |row| {
-      Ok(DebugPeople {
-        id: row.get(0)?,
-        name: row.get(1)?,
-        age: row.get(2)?,
-      })
-    })?;
-    let mut vec_people: Vec<DebugPeople> = Vec::new();
-    for p in rows { vec_people.push(p?); }
-    Ok(vec_people)
+
+fn filter_conditions(filter: &PeopleFilter) -> (Vec<String>, Vec<Value>) {
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    if let Some(name) = &filter.name_contains {
+        // % and _ are searched as text
+        let pattern = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        conditions.push("name LIKE ? ESCAPE '\\'".to_string());
+        params.push(Value::Text(format!("%{}%", pattern)));
+    }
+    if let Some(min_age) = filter.min_age {
+        conditions.push("age >= ?".to_string());
+        params.push(Value::Integer(min_age));
+    }
+    if let Some(max_age) = filter.max_age {
+        conditions.push("age <= ?".to_string());
+        params.push(Value::Integer(max_age));
+    }
+    (conditions, params)
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() { String::new() } else { format!(" WHERE {}", conditions.join(" AND ")) }
+}
+
+// rows after the values of the first `len` keys in the order of the keys, like
+// (a > ?1) OR (a = ?1 AND b < ?2) for `a ASC, b DESC`. each value is bound once and
+// referenced by its number, after the `skipped` params of the filters
+fn keyset_condition(keys: &[(PeopleField, bool)], len: usize, skipped: usize) -> String {
+    let param = |i: usize| format!("?{}", skipped + i + 1);
+    let alternatives: Vec<String> = (0..len.min(keys.len()))
+        .map(|i| {
+            let mut parts: Vec<String> = (0..i).map(|j| format!("{} = {}", keys[j].0.column(), param(j))).collect();
+            let (field, descending) = keys[i];
+            parts.push(format!("{} {} {}", field.column(), if descending { "<" } else { ">" }, param(i)));
+            format!("({})", parts.join(" AND "))
+        })
+        .collect();
+    format!("({})", alternatives.join(" OR "))
+}
+
+impl PeopleField {
+    pub fn column(&self) -> &'static str {
+        match self {
+            PeopleField::Id => "id",
+            PeopleField::Name => "name",
+            PeopleField::Age => "age",
+        }
+    }
+}
+
+impl People {
+    // values of the sort keys of this row, saved in the cursors
+    pub fn sort_values(&self, sort: &[SortKey]) -> Vec<Value> {
+        sort.iter()
+            .map(|key| match key.field {
+                PeopleField::Id => Value::Integer(self.id),
+                PeopleField::Name => Value::Text(self.name.clone()),
+                PeopleField::Age => Value::Integer(self.age),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn connections_are_reused_and_a_busy_pool_is_unavailable() {
+        let dir = env::temp_dir().join(format!("http-server-database-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let settings = PoolSettings { size: 1, acquire_timeout: Duration::from_millis(50), ..PoolSettings::default() };
+        let pool = ConnectionPool::open(&dir.join("people.db"), &settings).unwrap();
+
+        let conn = pool.get().unwrap();
+        let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+        assert!(matches!(pool.get(), Err(ServerError::Unavailable(_))));
+
+        // a transaction left open is rolled back when the connection is given back
+        conn.execute_batch("BEGIN").unwrap();
+        insert_new_people(&conn, "ana", 17).unwrap();
+        drop(conn);
+        let conn = pool.get().unwrap();
+        assert!(conn.is_autocommit());
+        assert!(fetch_people_by_name(&conn, "ana").unwrap().is_none());
+        drop(conn);
+
+        // a connection closed after an error is opened again
+        let mut conn = pool.get().unwrap();
+        drop(conn.conn.take());
+        pool.discard();
+        drop(conn);
+        let conn = pool.get().unwrap();
+        assert!(fetch_people_by_name(&conn, "ana").unwrap().is_none());
+        assert!(matches!(pool.get(), Err(ServerError::Unavailable(_))));
+        drop(conn);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,151 @@
+use std::fmt;
+use std::io;
+use serde_json::{json, Value};
+use crate::request::ParseError;
+use crate::response::{Response, IntoResponse};
+use crate::response_message::StatusCode;
+use crate::validation::ValidationErrors;
+
+// errors of the server and the handlers, each one is sent with its status code
+// and a problem details body (RFC 7807)
+#[derive(Debug)]
+pub enum ServerError {
+    // the request couldn't be read or parsed
+    Parse(ParseError),
+    BadRequest(String),
+    // the request was parsed but some fields are invalid, sent in the `errors` member
+    Validation(ValidationErrors),
+    NotFound(String),
+    // the path exists but doesn't accept the method, sent with the `Allow` header
+    MethodNotAllowed { allow: String },
+    // the method isn't supported by the server
+    NotImplemented(String),
+    Conflict(String),
+    // the resource can't be created because it conflicts with a stored one,
+    // sent with its id and url in the `existing_id` and `existing` members
+    AlreadyExists { detail: String, id: i64, location: String },
+    Database(rusqlite::Error),
+    Io(io::Error),
+    // the server can't handle the request now, like when it is overloaded
+    Unavailable(String),
+    // unexpected failure, like a handler that panicked
+    Internal(String),
+}
+
+impl ServerError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServerError::Parse(err) => err.status_code().unwrap_or(StatusCode::BadRequest),
+            ServerError::BadRequest(_) => StatusCode::BadRequest,
+            ServerError::Validation(_) => StatusCode::UnprocessableEntity,
+            ServerError::NotFound(_) => StatusCode::NotFound,
+            ServerError::MethodNotAllowed { .. } => StatusCode::MethodNotAllowed,
+            ServerError::NotImplemented(_) => StatusCode::NotImplemented,
+            ServerError::Conflict(_) | ServerError::AlreadyExists { .. } => StatusCode::Conflict,
+            ServerError::Unavailable(_) => StatusCode::ServiceUnavailable,
+            ServerError::Database(_) | ServerError::Io(_) | ServerError::Internal(_) => StatusCode::InternalServerError,
+        }
+    }
+    // message sent to the client, the details of the internal errors are only logged
+    fn detail(&self) -> String {
+        match self {
+            ServerError::Database(_) => "an error ocurred in the database".to_string(),
+            ServerError::Io(_) | ServerError::Internal(_) => "an internal error ocurred".to_string(),
+            ServerError::Validation(_) => "the request has invalid fields".to_string(),
+            err => err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Parse(err) => write!(f, "{}", err),
+            ServerError::BadRequest(msg) => write!(f, "{}", msg),
+            ServerError::Validation(errors) => write!(f, "the request has invalid fields: {}", errors),
+            ServerError::NotFound(msg) => write!(f, "{}", msg),
+            ServerError::MethodNotAllowed { allow } => write!(f, "method not allowed, use {}", allow),
+            ServerError::NotImplemented(msg) => write!(f, "{}", msg),
+            ServerError::Conflict(msg) => write!(f, "{}", msg),
+            ServerError::AlreadyExists { detail, .. } => write!(f, "{}", detail),
+            ServerError::Unavailable(msg) => write!(f, "{}", msg),
+            ServerError::Database(err) => write!(f, "database error: {}", err),
+            ServerError::Io(err) => write!(f, "io error: {}", err),
+            ServerError::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl From<ParseError> for ServerError {
+    fn from(err: ParseError) -> Self {
+        ServerError::Parse(err)
+    }
+}
+// a violated constraint, like a repeated unique name, is a conflict with the stored data
+impl From<rusqlite::Error> for ServerError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err {
+            rusqlite::Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation => {
+                ServerError::Conflict("the data conflicts with a stored value".to_string())
+            },
+            // another connection held the lock for longer than the busy timeout
+            rusqlite::Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::DatabaseBusy => {
+                ServerError::Unavailable("the database is busy, try again later".to_string())
+            },
+            err => ServerError::Database(err),
+        }
+    }
+}
+impl From<io::Error> for ServerError {
+    fn from(err: io::Error) -> Self {
+        ServerError::Io(err)
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let status_code = self.status_code();
+        if status_code.is_server_error() {
+            log_error!("an error ocurred to handle the request!\n{}", self);
+        }
+        let mut body = problem_body(status_code, &self.detail());
+        match &self {
+            ServerError::Validation(errors) => body["errors"] = json!(errors),
+            ServerError::AlreadyExists { id, location, .. } => {
+                body["existing_id"] = json!(id);
+                body["existing"] = json!(location);
+            },
+            _ => {},
+        }
+        let mut response = problem_response(status_code, &body);
+        if status_code == StatusCode::ServiceUnavailable {
+            let _ = response.headers.insert("Retry-After", "1");
+        }
+        if let ServerError::MethodNotAllowed { allow } = &self {
+            let _ = response.headers.insert("Allow", allow);
+        }
+        response
+    }
+}
+
+// problem details response, with the reason phrase of the status code as title
+pub fn problem(status_code: StatusCode, detail: &str) -> Response {
+    problem_response(status_code, &problem_body(status_code, detail))
+}
+
+fn problem_body(status_code: StatusCode, detail: &str) -> Value {
+    json!({
+        "type": "about:blank",
+        "title": status_code.reason_phrase(),
+        "status": status_code.as_u16(),
+        "detail": detail,
+    })
+}
+fn problem_response(status_code: StatusCode, body: &Value) -> Response {
+    Response::builder()
+        .status(status_code)
+        .content_type("application/problem+json")
+        .json(body)
+}
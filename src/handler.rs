@@ -0,0 +1,229 @@
+use std::marker::PhantomData;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::error::{problem, ServerError};
+use crate::headers::HeaderMap;
+use crate::request::{Request, percent_decode};
+use crate::response::{Response, IntoResponse};
+use crate::response_message::StatusCode;
+use crate::validation::ValidationErrors;
+
+// error of an extractor, sent to the client instead of calling the handler
+#[derive(Debug)]
+pub struct Rejection {
+    pub status_code: StatusCode,
+    pub message: String,
+    // fields that failed the validation, see `Valid`
+    pub errors: Option<ValidationErrors>,
+}
+
+// types that can be built from the request, used as arguments of the handlers
+pub trait FromRequest: Sized {
+    fn from_request(request: &Request) -> Result<Self, Rejection>;
+}
+
+// functions that can handle a request and return a value that implements IntoResponse.
+// `Args` lists the return type and the extractors of the function, like
+// `fn create(Json(people): Json<People>) -> (StatusCode, String)`, or is `RawRequest`
+// for functions that receive the whole request
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, request: &Request) -> Response;
+}
+
+// marker for the handlers that receive `&Request`
+pub struct RawRequest<R>(PhantomData<fn() -> R>);
+
+// body of the request parsed from json, the Content-Type must be application/json.
+// returned by a handler the value is sent as a json body
+pub struct Json<T>(pub T);
+// params of the route, like the id of /people/{id}. a route with a single param can be
+// extracted to a single value, like Path<i64>, and multiple params to a struct
+pub struct Path<T>(pub T);
+// query string parsed to a struct, like ?name=kevin&age=17
+pub struct Query<T>(pub T);
+pub struct Headers(pub HeaderMap);
+
+impl Rejection {
+    pub fn new(status_code: StatusCode, message: impl Into<String>) -> Self {
+        Rejection { status_code, message: message.into(), errors: None }
+    }
+    pub fn invalid(errors: ValidationErrors) -> Self {
+        Rejection { status_code: StatusCode::UnprocessableEntity, message: errors.to_string(), errors: Some(errors) }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        if let Some(errors) = self.errors {
+            return ServerError::Validation(errors).into_response();
+        }
+        problem(self.status_code, &self.message)
+    }
+}
+
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(request: &Request) -> Result<Self, Rejection> {
+        let mime_type = request.headers.mime_type().unwrap_or_default();
+        if mime_type != "application/json" && !mime_type.ends_with("+json") {
+            return Err(Rejection::new(StatusCode::UnsupportedMediaType, "expected a request with Content-Type: application/json"));
+        }
+        match serde_json::from_slice(&request.body) {
+            Ok(value) => Ok(Json(value)),
+            // valid json with fields that don't match the expected ones
+            Err(err) if err.is_data() => Err(Rejection::new(StatusCode::UnprocessableEntity, err.to_string())),
+            Err(err) => Err(Rejection::new(StatusCode::BadRequest, format!("invalid json body: {}", err))),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> FromRequest for Path<T> {
+    fn from_request(request: &Request) -> Result<Self, Rejection> {
+        let invalid = |err: String| Rejection::new(StatusCode::BadRequest, format!("invalid path params: {}", err));
+        // the params are percent-decoded and encoded again as a form to be parsed by serde
+        let mut params: Vec<(&str, String)> = Vec::new();
+        for (name, value) in &request.params {
+            let decoded = percent_decode(value).ok_or_else(|| invalid(format!("malformed encoding in {}", name)))?;
+            params.push((name, String::from_utf8_lossy(&decoded).to_string()));
+        }
+        let encoded = serde_urlencoded::to_string(&params).map_err(|err| invalid(err.to_string()))?;
+        if request.params.len() == 1 {
+            // a single param is parsed as a value, falling back to a struct with one field.
+            // the error of the value is reported when both fail
+            return match serde_urlencoded::from_str::<Vec<(String, T)>>(&encoded) {
+                Ok(mut values) => Ok(Path(values.remove(0).1)),
+                Err(err) => serde_urlencoded::from_str(&encoded).map(Path).map_err(|_| invalid(format!("{}: {}", params[0].0, err))),
+            };
+        }
+        serde_urlencoded::from_str(&encoded).map(Path).map_err(|err| invalid(err.to_string()))
+    }
+}
+
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    fn from_request(request: &Request) -> Result<Self, Rejection> {
+        serde_urlencoded::from_str(request.query().unwrap_or(""))
+            .map(Query)
+            .map_err(|err| Rejection::new(StatusCode::BadRequest, format!("invalid query string: {}", err)))
+    }
+}
+
+impl FromRequest for Headers {
+    fn from_request(request: &Request) -> Result<Self, Rejection> {
+        Ok(Headers(request.headers.clone()))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        Response::builder().json(&self.0)
+    }
+}
+
+impl<F, R> Handler<RawRequest<R>> for F
+where F: Fn(&Request) -> R + Send + Sync + 'static, R: IntoResponse {
+    fn call(&self, request: &Request) -> Response {
+        self(request).into_response()
+    }
+}
+
+// implements Handler for the functions with the extractors as arguments
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg,)*> Handler<(R, $($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($arg: FromRequest + 'static,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, request: &Request) -> Response {
+                $(
+                    let $arg = match $arg::from_request(request) {
+                        Ok(value) => value,
+                        Err(rejection) => return rejection.into_response(),
+                    };
+                )*
+                self($($arg),*).into_response()
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct People {
+        name: String,
+        age: u8,
+    }
+
+    fn request(content_type: Option<&str>, body: &str, params: &[(&str, &str)]) -> Request {
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = content_type {
+            headers.insert("Content-Type", content_type).unwrap();
+        }
+        Request {
+            method: crate::request::RequestMethod::POST, target: "/people".to_string(), version: "HTTP/1.1".to_string(),
+            headers, body: body.as_bytes().to_vec(), trailers: HeaderMap::new(),
+            params: params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+        }
+    }
+
+    fn create(Json(people): Json<People>) -> String {
+        format!("{} {}", people.name, people.age)
+    }
+    fn find(Path(id): Path<i64>) -> String {
+        id.to_string()
+    }
+
+    fn status(response: &Response) -> (StatusCode, Option<&str>) {
+        (response.status_code, response.headers.get("Content-Type"))
+    }
+
+    #[test]
+    fn json_accepts_the_json_media_types() {
+        for content_type in ["application/json", "Application/JSON; charset=utf-8", "application/merge-patch+json"] {
+            let response = Handler::call(&create, &request(Some(content_type), r#"{"name": "ana", "age": 17}"#, &[]));
+            assert_eq!(response.status_code, StatusCode::Success, "{}", content_type);
+        }
+    }
+
+    #[test]
+    fn json_rejects_other_content_types_with_415() {
+        for content_type in [None, Some("text/plain"), Some("application/x-www-form-urlencoded")] {
+            let response = Handler::call(&create, &request(content_type, r#"{"name": "ana", "age": 17}"#, &[]));
+            assert_eq!(status(&response), (StatusCode::UnsupportedMediaType, Some("application/problem+json")), "{:?}", content_type);
+        }
+    }
+
+    #[test]
+    fn malformed_json_is_rejected_with_400_and_wrong_types_with_422() {
+        for body in ["", r#"{"name": "ana""#, "{name: ana}"] {
+            let response = Handler::call(&create, &request(Some("application/json"), body, &[]));
+            assert_eq!(status(&response), (StatusCode::BadRequest, Some("application/problem+json")), "{}", body);
+        }
+        for body in [r#"{"name": "ana", "age": "old"}"#, r#"{"name": "ana", "age": 300}"#, r#"{"name": "ana"}"#, "[]"] {
+            let response = Handler::call(&create, &request(Some("application/json"), body, &[]));
+            assert_eq!(status(&response), (StatusCode::UnprocessableEntity, Some("application/problem+json")), "{}", body);
+        }
+    }
+
+    #[test]
+    fn path_params_that_dont_parse_are_rejected_with_400() {
+        let response = Handler::call(&find, &request(None, "", &[("id", "%2D7")]));
+        assert_eq!(response.status_code, StatusCode::Success);
+        for id in ["abc", "1.5", "", "99999999999999999999", "%ZZ"] {
+            let response = Handler::call(&find, &request(None, "", &[("id", id)]));
+            assert_eq!(status(&response), (StatusCode::BadRequest, Some("application/problem+json")), "{}", id);
+        }
+    }
+}
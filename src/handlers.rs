@@ -0,0 +1,584 @@
+use std::sync::Arc;
+use crate::config::ServerConfig;
+use crate::database::{self, PageStart, PeopleField, PeopleFilter, PeopleQuery, SortKey};
+use crate::error::ServerError;
+use crate::handler::{Json, Path, Query};
+use crate::pagination::{Cursor, link_header};
+use crate::pool::PoolStats;
+use crate::request::Request;
+use crate::response::Response;
+use crate::response_message::StatusCode;
+use crate::router::Router;
+use crate::static_files::StaticFiles;
+use crate::validation::{Field, Integer, Valid, Validate, ValidationErrors};
+use rusqlite::Connection;
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+// size of the pages of GET /people
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+// query string of GET /people, like ?limit=10&min_age=18&sort=age,-name
+#[derive(Debug, Deserialize)]
+struct ListPeople {
+    limit: Option<usize>,
+    offset: Option<usize>,
+    cursor: Option<String>,
+    name_contains: Option<String>,
+    min_age: Option<i64>,
+    max_age: Option<i64>,
+    sort: Option<String>,
+}
+#[derive(Debug, Serialize)]
+struct PeoplePage {
+    data: Vec<database::People>,
+    page: PageMeta,
+}
+#[derive(Debug, Serialize)]
+struct PageMeta {
+    limit: usize,
+    // only sent in the pages requested by offset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    // people that match the filters, in all the pages
+    total: i64,
+    next_cursor: Option<String>,
+    prev_cursor: Option<String>,
+}
+// body of POST /people and PUT /people/{id}, the fields are optional
+// to be reported by the validation when they are missing
+#[derive(Debug, Deserialize)]
+struct NewPeople {
+    name: Option<String>,
+    age: Option<Integer>,
+}
+// body of PATCH /people/{id}, the missing fields keep their values
+#[derive(Debug, Deserialize)]
+struct PeoplePatch {
+    name: Option<String>,
+    age: Option<Integer>,
+}
+// body of PUT /people/by-name/{name}, the name is taken from the path
+#[derive(Debug, Deserialize)]
+struct PeopleUpsert {
+    age: Option<Integer>,
+}
+// limits of the fields of people
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_AGE: i64 = 150;
+
+impl Validate for NewPeople {
+    fn validate(&self, errors: &mut ValidationErrors) {
+        validate_name(errors, self.name.as_ref()).required();
+        validate_age(errors, self.age.as_ref()).required();
+    }
+}
+impl Validate for PeopleUpsert {
+    fn validate(&self, errors: &mut ValidationErrors) {
+        validate_age(errors, self.age.as_ref()).required();
+    }
+}
+impl Validate for PeoplePatch {
+    fn validate(&self, errors: &mut ValidationErrors) {
+        validate_name(errors, self.name.as_ref());
+        validate_age(errors, self.age.as_ref());
+    }
+}
+fn validate_name<'a>(errors: &'a mut ValidationErrors, name: Option<&'a String>) -> Field<'a, String> {
+    errors.field("name", name)
+        .length(1, MAX_NAME_LENGTH)
+        .pattern("printable characters", |c| !c.is_control())
+}
+fn validate_age<'a>(errors: &'a mut ValidationErrors, age: Option<&'a Integer>) -> Field<'a, i64> {
+    errors.integer("age", age).range(0, MAX_AGE)
+}
+
+impl NewPeople {
+    // the values are present after the validation
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_default().trim()
+    }
+    fn age(&self) -> i64 {
+        self.age.as_ref().and_then(Integer::value).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CalcRequest {
+    operation: String,
+    number1: f64,
+    number2: f64,
+}
+
+// routes of the application, the files of the document root are served in the static prefix
+// and the counters of the thread pool in /server-stats
+pub fn router(config: &ServerConfig, pool_stats: Arc<PoolStats>) -> Router {
+    let static_files = || StaticFiles::new(&config.document_root).with_symlink_policy(config.symlink_policy);
+    let pages = Arc::new(static_files());
+    let page = |file_name: &'static str| {
+        let pages = Arc::clone(&pages);
+        move |_: &Request| pages.serve(file_name)
+    };
+    Router::new()
+        .get("/", page("home.html"))
+        .get("/calculator", page("calc.html"))
+        .get("/create-people", page("people.html"))
+        .get("/server-stats", move |_: &Request| Json(pool_stats.snapshot()))
+        .mount_static(&config.static_prefix, static_files())
+        .post("/calculator", calculator)
+        .get("/people", list_people)
+        .post("/people", create_people)
+        .get("/people/{id}", get_people)
+        .put("/people/{id}", replace_people)
+        .patch("/people/{id}", update_people)
+        .delete("/people/{id}", delete_people)
+        .put("/people/by-name/{name}", upsert_people)
+}
+
+// a page of people, by offset or by the cursors of the previous pages.
+// the next and prev pages are linked in the Link header
+fn list_people(Query(params): Query<ListPeople>) -> Result<Response, ServerError> {
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(ServerError::BadRequest(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    if params.offset.is_some() && params.cursor.is_some() {
+        return Err(ServerError::BadRequest("offset and cursor can't be used together".to_string()));
+    }
+    let sort = parse_sort(params.sort.as_deref().unwrap_or("id"))?;
+    let cursor = match params.cursor.as_deref() {
+        Some(cursor) => Some(Cursor::decode(cursor)
+            .filter(|cursor| cursor_matches_sort(cursor, &sort))
+            .ok_or_else(|| ServerError::BadRequest("invalid cursor".to_string()))?),
+        None => None,
+    };
+    let offset = params.offset.unwrap_or(0);
+    // sqlite reads the offset as a signed 64 bits integer
+    if offset > i64::MAX as usize {
+        return Err(ServerError::BadRequest(format!("offset must be at most {}", i64::MAX)));
+    }
+    let start = match &cursor {
+        Some(cursor) if cursor.before => PageStart::Before(cursor.values.clone()),
+        Some(cursor) => PageStart::After(cursor.values.clone()),
+        None => PageStart::Offset(offset),
+    };
+    let filter = PeopleFilter {
+        name_contains: params.name_contains.clone(),
+        min_age: params.min_age,
+        max_age: params.max_age,
+    };
+
+    let conn = database::connect_db()?;
+    let total = database::count_people(&conn, &filter)?;
+    // one more row is read to know if there is another page
+    let query = PeopleQuery { filter, sort: sort.clone(), start, limit: limit + 1 };
+    let mut people = database::fetch_people_page(&conn, &query)?;
+    let has_more = people.len() > limit;
+    if has_more {
+        // the extra row is at the side where the page was read
+        if matches!(query.start, PageStart::Before(_)) { people.remove(0); } else { people.pop(); }
+    }
+    let (has_next, has_prev) = match &cursor {
+        Some(cursor) if cursor.before => (true, has_more),
+        Some(_) => (has_more, true),
+        None => (has_more, offset > 0),
+    };
+    let next_cursor = people.last().filter(|_| has_next).map(|p| Cursor::after(p.sort_values(&sort)).encode());
+    let prev_cursor = people.first().filter(|_| has_prev).map(|p| Cursor::before(p.sort_values(&sort)).encode());
+
+    // the links keep the filters and the sort of the request
+    let mut base_params = vec![("limit", limit.to_string())];
+    let filters = [("name_contains", params.name_contains), ("min_age", params.min_age.map(|a| a.to_string())),
+        ("max_age", params.max_age.map(|a| a.to_string())), ("sort", params.sort)];
+    base_params.extend(filters.into_iter().filter_map(|(name, value)| value.map(|v| (name, v))));
+    let link = |position: (&'static str, String)| {
+        let mut params = base_params.clone();
+        params.push(position);
+        params
+    };
+    let mut links = Vec::new();
+    if cursor.is_some() {
+        if let Some(next) = &next_cursor {
+            links.push(("next", link(("cursor", next.clone()))));
+        }
+        if let Some(prev) = &prev_cursor {
+            links.push(("prev", link(("cursor", prev.clone()))));
+        }
+    } else {
+        if has_next {
+            links.push(("next", link(("offset", offset.saturating_add(limit).to_string()))));
+        }
+        if has_prev {
+            links.push(("prev", link(("offset", offset.saturating_sub(limit).to_string()))));
+        }
+    }
+
+    let page = PageMeta { limit, offset: cursor.is_none().then_some(offset), total, next_cursor, prev_cursor };
+    let mut response = Response::builder();
+    if let Some(link) = link_header("/people", &links) {
+        response = response.header("Link", &link);
+    }
+    Ok(response.json(&PeoplePage { data: people, page }))
+}
+
+// sort keys like "age,-name", a - sorts the field in descending order.
+// the id is added as the last key when it isn't used
+fn parse_sort(sort: &str) -> Result<Vec<SortKey>, ServerError> {
+    let mut keys: Vec<SortKey> = Vec::new();
+    for key in sort.split(',').map(|k| k.trim()) {
+        let (name, descending) = match key.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (key.strip_prefix('+').unwrap_or(key), false),
+        };
+        let field = match name {
+            "id" => PeopleField::Id,
+            "name" => PeopleField::Name,
+            "age" => PeopleField::Age,
+            _ => return Err(ServerError::BadRequest(format!("can't sort by {:?}, use id, name or age", name))),
+        };
+        if keys.iter().any(|k| k.field == field) {
+            return Err(ServerError::BadRequest(format!("{} is repeated in the sort", name)));
+        }
+        keys.push(SortKey { field, descending });
+    }
+    if !keys.iter().any(|k| k.field == PeopleField::Id) {
+        keys.push(SortKey { field: PeopleField::Id, descending: false });
+    }
+    Ok(keys)
+}
+
+// the cursor has a value of the right type for each sort key
+fn cursor_matches_sort(cursor: &Cursor, sort: &[SortKey]) -> bool {
+    cursor.values.len() == sort.len() && cursor.values.iter().zip(sort).all(|(value, key)| {
+        matches!((key.field, value), (PeopleField::Name, Value::Text(_)) | (PeopleField::Id | PeopleField::Age, Value::Integer(_)))
+    })
+}
+
+fn create_people(Valid(Json(people)): Valid<Json<NewPeople>>) -> Result<Response, ServerError> {
+    let conn = database::connect_db()?;
+    let Some(id) = database::insert_new_people(&conn, people.name(), people.age())? else {
+        return Err(name_conflict(&conn, people.name()));
+    };
+    let people = database::fetch_people_by_id(&conn, id)?.ok_or_else(|| people_not_found(id))?;
+    Ok(people_created(&people))
+}
+
+// create the people with the name of the path or update its age, so sending
+// the same request again doesn't create another people
+fn upsert_people(Path(name): Path<String>, Valid(Json(upsert)): Valid<Json<PeopleUpsert>>) -> Result<Response, ServerError> {
+    let mut errors = ValidationErrors::new();
+    validate_name(&mut errors, Some(&name));
+    if !errors.is_empty() {
+        return Err(ServerError::Validation(errors));
+    }
+    let age = upsert.age.as_ref().and_then(Integer::value).unwrap_or_default();
+    let conn = database::connect_db()?;
+    match database::upsert_people(&conn, name.trim(), age)? {
+        (people, true) => Ok(people_created(&people)),
+        (people, false) => Ok(Response::builder().json(&people)),
+    }
+}
+
+fn people_created(people: &database::People) -> Response {
+    Response::builder()
+        .status(StatusCode::Created)
+        .header("Location", &format!("/people/{}", people.id))
+        .json(people)
+}
+
+fn get_people(Path(id): Path<i64>) -> Result<Json<database::People>, ServerError> {
+    let conn = database::connect_db()?;
+    fetch_people(&conn, id)
+}
+
+fn replace_people(Path(id): Path<i64>, Valid(Json(people)): Valid<Json<NewPeople>>) -> Result<Json<database::People>, ServerError> {
+    let conn = database::connect_db()?;
+    check_name_available(&conn, people.name(), id)?;
+    if !database::update_people(&conn, id, people.name(), people.age())? {
+        return Err(people_not_found(id));
+    }
+    fetch_people(&conn, id)
+}
+
+fn update_people(Path(id): Path<i64>, Valid(Json(patch)): Valid<Json<PeoplePatch>>) -> Result<Json<database::People>, ServerError> {
+    let conn = database::connect_db()?;
+    let people = database::fetch_people_by_id(&conn, id)?.ok_or_else(|| people_not_found(id))?;
+    let name = patch.name.as_deref().map_or(people.name.as_str(), str::trim);
+    let age = patch.age.as_ref().and_then(Integer::value).unwrap_or(people.age);
+    check_name_available(&conn, name, id)?;
+    if !database::update_people(&conn, id, name, age)? {
+        return Err(people_not_found(id));
+    }
+    fetch_people(&conn, id)
+}
+
+// the handlers read the people with the connection they hold, taking a second one
+// of the pool could wait for itself when the pool is exhausted
+fn fetch_people(conn: &Connection, id: i64) -> Result<Json<database::People>, ServerError> {
+    database::fetch_people_by_id(conn, id)?.map(Json).ok_or_else(|| people_not_found(id))
+}
+
+fn delete_people(Path(id): Path<i64>) -> Result<StatusCode, ServerError> {
+    let conn = database::connect_db()?;
+    if !database::delete_people(&conn, id)? {
+        return Err(people_not_found(id));
+    }
+    Ok(StatusCode::NoContent)
+}
+
+// the name can be used by the people `id` when no other people has it
+fn check_name_available(conn: &Connection, name: &str, id: i64) -> Result<(), ServerError> {
+    match database::fetch_people_by_name(conn, name)? {
+        Some(people) if people.id != id => Err(name_used_by(&people)),
+        _ => Ok(()),
+    }
+}
+// conflict with the people that has the name
+fn name_conflict(conn: &Connection, name: &str) -> ServerError {
+    match database::fetch_people_by_name(conn, name) {
+        Ok(Some(people)) => name_used_by(&people),
+        // the people was deleted after the insert failed
+        Ok(None) => ServerError::Conflict(format!("the name {} is already used", name)),
+        Err(err) => err.into(),
+    }
+}
+fn name_used_by(people: &database::People) -> ServerError {
+    ServerError::AlreadyExists {
+        detail: format!("the name {} is already used", people.name),
+        id: people.id,
+        location: format!("/people/{}", people.id),
+    }
+}
+
+fn people_not_found(id: i64) -> ServerError {
+    ServerError::NotFound(format!("the people {} doesn't exist", id))
+}
+
+fn calculator(Json(calc): Json<CalcRequest>) -> Result<String, ServerError> {
+    let number1 = calc.number1;
+    let number2 = calc.number2;
+
+    match calc.operation.as_str() {
+        "sum" => Ok(format!("the sum is: {}", number1 + number2)),
+        "sub" => Ok(format!("the sub is: {}", number1 - number2)),
+        "mult" => Ok(format!("the mult is: {}", number1 * number2)),
+        "div" => Ok(format!("the div is: {}", number1 / number2)),
+        _ => Err(ServerError::Validation(ValidationErrors::single("operation", "must be sum, sub, mult or div"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Once;
+    use serde_json::{json, Value as JsonValue};
+    use crate::database::PoolSettings;
+    use crate::headers::HeaderMap;
+    use crate::request::RequestMethod;
+    use crate::response::Body;
+
+    // the handlers share the pool of the process, so every test uses its own names
+    fn app() -> Router {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("http-server-handlers-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            database::init(&dir.join("people.db"), &PoolSettings::default()).unwrap();
+        });
+        router(&ServerConfig::default(), Arc::new(PoolStats::default()))
+    }
+
+    struct Sent {
+        status: StatusCode,
+        headers: HeaderMap,
+        body: JsonValue,
+    }
+
+    fn send(app: &Router, method: RequestMethod, target: &str, body: JsonValue) -> Sent {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "application/json").unwrap();
+        let mut request = Request {
+            method,
+            target: target.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers,
+            body: if body.is_null() { Vec::new() } else { body.to_string().into_bytes() },
+            trailers: HeaderMap::new(),
+            params: HashMap::new(),
+        };
+        let route = app.find(method, request.path()).expect("the route exists");
+        request.params = route.params;
+        let response = (route.handler)(&request);
+        let body = match response.body {
+            Body::Full(body) if !body.is_empty() => serde_json::from_slice(&body).unwrap(),
+            _ => JsonValue::Null,
+        };
+        Sent { status: response.status_code, headers: response.headers, body }
+    }
+    fn get(app: &Router, target: &str) -> Sent {
+        send(app, RequestMethod::GET, target, JsonValue::Null)
+    }
+    fn create(app: &Router, name: &str, age: i64) -> i64 {
+        let sent = send(app, RequestMethod::POST, "/people", json!({ "name": name, "age": age }));
+        assert_eq!(sent.status, StatusCode::Created, "{}", sent.body);
+        sent.body["id"].as_i64().unwrap()
+    }
+    fn names(sent: &Sent) -> Vec<&str> {
+        sent.body["data"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn list_is_paginated_by_offset_with_links() {
+        let app = app();
+        for (name, age) in [("offset-a", 1), ("offset-b", 2), ("offset-c", 3), ("offset-d", 4), ("offset-e", 5)] {
+            create(&app, name, age);
+        }
+        let page = get(&app, "/people?name_contains=offset-&limit=2&sort=name");
+        assert_eq!(names(&page), ["offset-a", "offset-b"]);
+        assert_eq!(page.body["page"]["total"], 5);
+        assert_eq!(page.headers.get("link"), Some("</people?limit=2&name_contains=offset-&sort=name&offset=2>; rel=\"next\""));
+
+        let page = get(&app, "/people?name_contains=offset-&limit=2&sort=name&offset=2");
+        assert_eq!(names(&page), ["offset-c", "offset-d"]);
+        assert_eq!(page.headers.get("link"), Some(concat!(
+            "</people?limit=2&name_contains=offset-&sort=name&offset=4>; rel=\"next\", ",
+            "</people?limit=2&name_contains=offset-&sort=name&offset=0>; rel=\"prev\"",
+        )));
+
+        let page = get(&app, "/people?name_contains=offset-&limit=2&sort=name&offset=4");
+        assert_eq!(names(&page), ["offset-e"]);
+        assert_eq!(page.body["page"]["next_cursor"], JsonValue::Null);
+        assert_eq!(get(&app, "/people?offset=9223372036854775808").status, StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn cursors_walk_the_pages_forward_and_back() {
+        let app = app();
+        let people = [("cursor-a", 30), ("cursor-b", 20), ("cursor-c", 30), ("cursor-d", 20), ("cursor-e", 40)];
+        let ids: Vec<i64> = people.iter().map(|(name, age)| create(&app, name, *age)).collect();
+        let expected = ["cursor-e", "cursor-a", "cursor-c", "cursor-b", "cursor-d"];
+
+        // -age,name with the next cursors, then back with the prev cursors
+        let base = "/people?name_contains=cursor-&limit=2&sort=-age,name";
+        let mut pages = vec![get(&app, base)];
+        while let Some(next) = pages.last().unwrap().body["page"]["next_cursor"].as_str().map(str::to_string) {
+            let page = get(&app, &format!("{}&cursor={}", base, next));
+            assert!(page.headers.get("link").unwrap().contains("rel=\"prev\""));
+            pages.push(page);
+        }
+        let forward: Vec<&str> = pages.iter().flat_map(names).collect();
+        assert_eq!(forward, expected);
+
+        let mut backward = names(pages.last().unwrap()).into_iter().map(str::to_string).collect::<Vec<String>>();
+        let mut prev = pages.last().unwrap().body["page"]["prev_cursor"].as_str().map(str::to_string);
+        while let Some(cursor) = prev {
+            let page = get(&app, &format!("{}&cursor={}", base, cursor));
+            backward.splice(0..0, names(&page).into_iter().map(str::to_string));
+            prev = page.body["page"]["prev_cursor"].as_str().map(str::to_string);
+        }
+        assert_eq!(backward, expected);
+
+        // the people with the same age are sorted by id
+        let base = "/people?name_contains=cursor-&limit=1&sort=age";
+        let mut page = get(&app, base);
+        let mut walked = Vec::new();
+        loop {
+            walked.push(page.body["data"][0]["id"].as_i64().unwrap());
+            let Some(next) = page.body["page"]["next_cursor"].as_str().map(str::to_string) else { break };
+            page = get(&app, &format!("{}&cursor={}", base, next));
+        }
+        assert_eq!(walked, [ids[1], ids[3], ids[0], ids[2], ids[4]]);
+    }
+
+    #[test]
+    fn name_filter_searches_wildcards_as_text() {
+        let app = app();
+        for name in ["pct-100%", "pct-1000", "und_x", "undyx"] {
+            create(&app, name, 1);
+        }
+        assert_eq!(names(&get(&app, "/people?name_contains=pct-100%25")), ["pct-100%"]);
+        assert_eq!(names(&get(&app, "/people?name_contains=und_")), ["und_x"]);
+    }
+
+    #[test]
+    fn cursors_of_another_sort_are_rejected() {
+        let app = app();
+        create(&app, "sorted-a", 1);
+        create(&app, "sorted-b", 2);
+        let page = get(&app, "/people?name_contains=sorted-&limit=1&sort=name");
+        let cursor = page.body["page"]["next_cursor"].as_str().unwrap().to_string();
+        assert_eq!(names(&get(&app, &format!("/people?name_contains=sorted-&limit=1&sort=name&cursor={}", cursor))), ["sorted-b"]);
+        assert_eq!(get(&app, &format!("/people?sort=age&cursor={}", cursor)).status, StatusCode::BadRequest);
+        assert_eq!(get(&app, "/people?cursor=zz").status, StatusCode::BadRequest);
+        assert_eq!(get(&app, &format!("/people?offset=1&cursor={}", cursor)).status, StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn people_resource_answers_created_and_not_found() {
+        let app = app();
+        let created = send(&app, RequestMethod::POST, "/people", json!({ "name": "rest-ana", "age": "17" }));
+        assert_eq!(created.status, StatusCode::Created);
+        let id = created.body["id"].as_i64().unwrap();
+        assert_eq!(created.headers.get("location"), Some(format!("/people/{}", id).as_str()));
+        assert_eq!(created.body, json!({ "id": id, "name": "rest-ana", "age": 17 }));
+
+        let people = format!("/people/{}", id);
+        assert_eq!(get(&app, &people).body["name"], "rest-ana");
+        let replaced = send(&app, RequestMethod::PUT, &people, json!({ "name": "rest-ana", "age": 18 }));
+        assert_eq!((replaced.status, replaced.body["age"].as_i64()), (StatusCode::Success, Some(18)));
+        let patched = send(&app, RequestMethod::PATCH, &people, json!({ "age": 19 }));
+        assert_eq!((patched.status, patched.body["age"].as_i64()), (StatusCode::Success, Some(19)));
+        assert_eq!(send(&app, RequestMethod::DELETE, &people, JsonValue::Null).status, StatusCode::NoContent);
+
+        let missing = [
+            (RequestMethod::GET, JsonValue::Null),
+            (RequestMethod::PUT, json!({ "name": "rest-ana", "age": 18 })),
+            (RequestMethod::PATCH, json!({ "age": 19 })),
+            (RequestMethod::DELETE, JsonValue::Null),
+        ];
+        for (method, body) in missing {
+            let sent = send(&app, method, &people, body);
+            assert_eq!(sent.status, StatusCode::NotFound, "{}", method);
+            assert_eq!(sent.headers.get("content-type"), Some("application/problem+json"));
+        }
+    }
+
+    #[test]
+    fn used_names_conflict_with_the_existing_people() {
+        let app = app();
+        let ana = create(&app, "conflict-ana", 17);
+        let bob = create(&app, "conflict-bob", 20);
+        let existing = |sent: &Sent| (sent.status, sent.body["existing_id"].as_i64(), sent.body["existing"].as_str().map(str::to_string));
+        let expected = (StatusCode::Conflict, Some(ana), Some(format!("/people/{}", ana)));
+
+        let duplicate = send(&app, RequestMethod::POST, "/people", json!({ "name": "conflict-ana", "age": 30 }));
+        assert_eq!(existing(&duplicate), expected);
+        let renamed = send(&app, RequestMethod::PUT, &format!("/people/{}", bob), json!({ "name": "conflict-ana", "age": 20 }));
+        assert_eq!(existing(&renamed), expected);
+        let renamed = send(&app, RequestMethod::PATCH, &format!("/people/{}", bob), json!({ "name": "conflict-ana" }));
+        assert_eq!(existing(&renamed), expected);
+        // keeping its own name isn't a conflict
+        let kept = send(&app, RequestMethod::PUT, &format!("/people/{}", ana), json!({ "name": "conflict-ana", "age": 18 }));
+        assert_eq!(kept.status, StatusCode::Success);
+    }
+
+    #[test]
+    fn upsert_by_name_creates_then_updates() {
+        let app = app();
+        let created = send(&app, RequestMethod::PUT, "/people/by-name/upsert%20carl", json!({ "age": "40" }));
+        assert_eq!(created.status, StatusCode::Created);
+        let id = created.body["id"].as_i64().unwrap();
+        assert_eq!(created.headers.get("location"), Some(format!("/people/{}", id).as_str()));
+        assert_eq!(created.body["name"], "upsert carl");
+
+        let updated = send(&app, RequestMethod::PUT, "/people/by-name/upsert%20carl", json!({ "age": 41 }));
+        assert_eq!(updated.status, StatusCode::Success);
+        assert_eq!(updated.body, json!({ "id": id, "name": "upsert carl", "age": 41 }));
+        let invalid = send(&app, RequestMethod::PUT, "/people/by-name/upsert%20carl", json!({ "age": -1 }));
+        assert_eq!(invalid.status, StatusCode::UnprocessableEntity);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+// header fields of a request or response. names are compared ignoring the case,
+// the fields keep the order they were added in and a name can be repeated, like Set-Cookie
+#[derive(Debug, Clone, Default)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    InvalidName(String),
+    InvalidValue(String),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::InvalidName(name) => write!(f, "invalid header name: {:?}", name),
+            HeaderError::InvalidValue(value) => write!(f, "invalid header value: {:?}", value),
+        }
+    }
+}
+
+// characters allowed in a header name (RFC 9110 token)
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+// a value can't have control characters other than tab, this blocks CR/LF injection
+pub fn is_valid_value(value: &str) -> bool {
+    value.bytes().all(|b| b == b'\t' || !b.is_ascii_control())
+}
+
+fn validate(name: &str, value: &str) -> Result<(), HeaderError> {
+    if !is_valid_name(name) {
+        return Err(HeaderError::InvalidName(name.to_string()));
+    }
+    if !is_valid_value(value) {
+        return Err(HeaderError::InvalidValue(value.to_string()));
+    }
+    Ok(())
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        HeaderMap { entries: Vec::new() }
+    }
+
+    // first value of the header
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries.iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // set the header, replacing all the values of the same name.
+    // the value keeps the position of the first replaced field
+    pub fn insert(&mut self, name: &str, value: &str) -> Result<(), HeaderError> {
+        validate(name, value)?;
+        let mut replaced = false;
+        self.entries.retain_mut(|(k, v)| {
+            if !k.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if replaced {
+                return false;
+            }
+            replaced = true;
+            *k = name.to_string();
+            *v = value.to_string();
+            true
+        });
+        if !replaced {
+            self.entries.push((name.to_string(), value.to_string()));
+        }
+        Ok(())
+    }
+    // add a value for the header, keeping the values that were set before
+    pub fn append(&mut self, name: &str, value: &str) -> Result<(), HeaderError> {
+        validate(name, value)?;
+        self.entries.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // check if a comma separated header, like Connection, has a token
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name)
+            .flat_map(|v| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+    pub fn content_type(&self) -> Option<&str> {
+        self.get("content-type")
+    }
+    // media type of the Content-Type, without parameters like charset
+    pub fn mime_type(&self) -> Option<String> {
+        self.content_type().map(|ct| ct.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+    }
+    pub fn content_length(&self) -> Option<u64> {
+        self.get("content-length").and_then(|v| v.trim().parse().ok())
+    }
+    pub fn location(&self) -> Option<&str> {
+        self.get("location")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(fields: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers.append(name, value).unwrap();
+        }
+        headers
+    }
+
+    #[test]
+    fn names_are_compared_ignoring_the_case() {
+        let headers = headers(&[("Content-Type", "text/html"), ("X-Custom", "a")]);
+        assert_eq!(headers.get("content-type"), Some("text/html"));
+        assert_eq!(headers.get("CONTENT-TYPE"), Some("text/html"));
+        assert!(headers.contains("x-custom"));
+        assert!(headers.get("Content-Length").is_none());
+    }
+
+    #[test]
+    fn repeated_names_keep_every_value_in_order() {
+        let headers = headers(&[("Set-Cookie", "a=1"), ("Date", "today"), ("set-cookie", "b=2")]);
+        assert_eq!(headers.get("Set-Cookie"), Some("a=1"));
+        assert_eq!(headers.get_all("SET-COOKIE").collect::<Vec<_>>(), ["a=1", "b=2"]);
+        assert_eq!(headers.iter().collect::<Vec<_>>(), [("Set-Cookie", "a=1"), ("Date", "today"), ("set-cookie", "b=2")]);
+    }
+
+    #[test]
+    fn insert_replaces_all_the_values_at_the_first_position() {
+        let mut headers = headers(&[("Vary", "Accept"), ("Date", "today"), ("vary", "Origin"), ("Server", "test")]);
+        headers.insert("VARY", "Cookie").unwrap();
+        assert_eq!(headers.iter().collect::<Vec<_>>(), [("VARY", "Cookie"), ("Date", "today"), ("Server", "test")]);
+
+        headers.insert("Allow", "GET").unwrap();
+        assert_eq!(headers.iter().last(), Some(("Allow", "GET")));
+        headers.remove("vary");
+        assert_eq!(headers.len(), 3);
+    }
+
+    #[test]
+    fn names_and_values_with_control_characters_are_rejected() {
+        let mut headers = HeaderMap::new();
+        for name in ["X-Bad\r\nSet-Cookie", "X-Bad\n", "X-Bad\0", "X Bad", "X-Bad:", ""] {
+            assert_eq!(headers.insert(name, "a"), Err(HeaderError::InvalidName(name.to_string())), "{:?}", name);
+            assert_eq!(headers.append(name, "a"), Err(HeaderError::InvalidName(name.to_string())), "{:?}", name);
+        }
+        for value in ["a\r\nSet-Cookie: admin=1", "a\rb", "a\nb", "a\0b", "a\x7fb"] {
+            assert_eq!(headers.insert("X-Value", value), Err(HeaderError::InvalidValue(value.to_string())), "{:?}", value);
+            assert_eq!(headers.append("X-Value", value), Err(HeaderError::InvalidValue(value.to_string())), "{:?}", value);
+        }
+        assert!(headers.is_empty());
+        headers.insert("X-Value", "a\tb; c=\"d\"").unwrap();
+        assert_eq!(headers.get("x-value"), Some("a\tb; c=\"d\""));
+    }
+
+    #[test]
+    fn has_token_looks_in_every_comma_separated_value() {
+        let headers = headers(&[("Connection", "keep-alive, Upgrade"), ("Connection", "close")]);
+        assert!(headers.has_token("connection", "upgrade"));
+        assert!(headers.has_token("Connection", "CLOSE"));
+        assert!(!headers.has_token("Connection", "keep"));
+    }
+}
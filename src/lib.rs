@@ -0,0 +1,19 @@
+#[macro_use]
+pub mod logger;
+pub mod config;
+pub mod server;
+pub mod pool;
+pub mod shutdown;
+pub mod headers;
+pub mod request;
+pub mod response;
+pub mod response_message;
+pub mod chunked;
+pub mod router;
+pub mod handler;
+pub mod error;
+pub mod validation;
+pub mod static_files;
+pub mod handlers;
+pub mod database;
+pub mod pagination;
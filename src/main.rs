@@ -1,8 +1,32 @@
-mod constants;
-mod server;
-mod response_message;
-mod database;
+use std::env;
+use std::process;
+use http_server::{server, handlers, database, logger};
+use http_server::config::{self, ServerConfig};
+use http_server::pool::ThreadPool;
 
 fn main() {
-  server::try_server_connect(constants::SERVER_PORT);
+  let args: Vec<String> = env::args().skip(1).collect();
+  if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+    print!("{}", config::usage());
+    return;
+  }
+  let config = match ServerConfig::load(args) {
+    Ok(config) => config,
+    Err(err) => {
+      eprintln!("{}\n\nrun with --help to see the settings", err);
+      process::exit(2);
+    }
+  };
+  logger::set_level(config.log_level);
+  if let Err(err) = database::init(&config.database_path, &config.database_pool) {
+    eprintln!("an error ocurred to open the database {}!\n{}", config.database_path.display(), err);
+    process::exit(1);
+  }
+
+  let pool = ThreadPool::new(config.workers, config.queue_size);
+  let router = handlers::router(&config, pool.stats());
+  if let Err(err) = server::try_server_connect(config, router, pool) {
+    eprintln!("an error ocurred to try the server connect!\n{}", err);
+    process::exit(1);
+  }
 }
@@ -0,0 +1,68 @@
+use rusqlite::types::Value;
+use serde_json::json;
+
+// position in a sorted list saved in the next and prev links, with the values
+// of the sort keys of the row where the page starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    // the page is after the row, or before it for the prev links
+    pub before: bool,
+    pub values: Vec<Value>,
+}
+
+impl Cursor {
+    pub fn after(values: Vec<Value>) -> Self {
+        Cursor { before: false, values }
+    }
+    pub fn before(values: Vec<Value>) -> Self {
+        Cursor { before: true, values }
+    }
+
+    // opaque text sent to the client, the hex of a json array
+    pub fn encode(&self) -> String {
+        let values: Vec<serde_json::Value> = self.values.iter()
+            .map(|value| match value {
+                Value::Integer(n) => json!(n),
+                Value::Text(s) => json!(s),
+                _ => serde_json::Value::Null,
+            })
+            .collect();
+        let cursor = json!([if self.before { "before" } else { "after" }, values]).to_string();
+        cursor.bytes().map(|b| format!("{:02x}", b)).collect()
+    }
+    // `None` when the cursor wasn't created by `encode`
+    pub fn decode(cursor: &str) -> Option<Self> {
+        if !cursor.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let (direction, values): (String, Vec<serde_json::Value>) = serde_json::from_slice(&bytes).ok()?;
+        let values = values.into_iter()
+            .map(|value| match value {
+                serde_json::Value::Number(n) => n.as_i64().map(Value::Integer),
+                serde_json::Value::String(s) => Some(Value::Text(s)),
+                _ => None,
+            })
+            .collect::<Option<Vec<Value>>>()?;
+        match direction.as_str() {
+            "after" => Some(Cursor::after(values)),
+            "before" => Some(Cursor::before(values)),
+            _ => None,
+        }
+    }
+}
+
+// value of a Link header (RFC 8288) with the target of each relation, like
+// </people?offset=20>; rel="next". `None` when there are no links
+pub fn link_header(path: &str, links: &[(&str, Vec<(&str, String)>)]) -> Option<String> {
+    let links: Vec<String> = links.iter()
+        .map(|(rel, params)| {
+            let query = serde_urlencoded::to_string(params).unwrap_or_default();
+            format!("<{}?{}>; rel=\"{}\"", path, query, rel)
+        })
+        .collect();
+    (!links.is_empty()).then(|| links.join(", "))
+}
@@ -0,0 +1,178 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use serde::Serialize;
+
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// fixed number of threads running the jobs of a bounded queue.
+// dropping the pool waits for the queued jobs to finish
+pub struct ThreadPool {
+    workers: Vec<JoinHandle<()>>,
+    sender: Option<SyncSender<Job>>,
+    stats: Arc<PoolStats>,
+}
+
+// counters of the pool, shared with the workers
+#[derive(Debug, Default)]
+pub struct PoolStats {
+    workers: AtomicUsize,
+    busy: AtomicUsize,
+    queued: AtomicUsize,
+    completed: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+// values of the counters in a moment, sent by the monitoring route
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PoolSnapshot {
+    pub workers: usize,
+    pub busy: usize,
+    pub queued: usize,
+    pub completed: usize,
+    pub rejected: usize,
+}
+
+impl PoolStats {
+    pub fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot {
+            workers: self.workers.load(Ordering::Relaxed),
+            busy: self.busy.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ThreadPool {
+    // `queue_size` jobs can wait for a free worker, more than that are rejected by `try_execute`
+    pub fn new(workers: usize, queue_size: usize) -> Self {
+        assert!(workers > 0, "the pool needs at least one worker");
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let stats = Arc::new(PoolStats::default());
+        stats.workers.store(workers, Ordering::Relaxed);
+        let workers = (0..workers)
+            .map(|id| {
+                let receiver = Arc::clone(&receiver);
+                let stats = Arc::clone(&stats);
+                thread::Builder::new()
+                    .name(format!("worker-{}", id))
+                    .spawn(move || worker_loop(&receiver, &stats))
+                    .expect("an error ocurred to spawn the worker thread!")
+            })
+            .collect();
+        ThreadPool { workers, sender: Some(sender), stats }
+    }
+
+    pub fn stats(&self) -> Arc<PoolStats> {
+        Arc::clone(&self.stats)
+    }
+
+    // queue the job, giving it back when the queue is full
+    pub fn try_execute(&self, job: Job) -> Result<(), Job> {
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        match self.sender().try_send(job) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(job)) | Err(TrySendError::Disconnected(job)) => {
+                self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(job)
+            }
+        }
+    }
+    // queue the job, waiting for space in the queue when it is full
+    pub fn execute(&self, job: Job) {
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        if self.sender().send(job).is_err() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn sender(&self) -> &SyncSender<Job> {
+        self.sender.as_ref().expect("the sender is only dropped with the pool")
+    }
+}
+
+fn worker_loop(receiver: &Mutex<Receiver<Job>>, stats: &PoolStats) {
+    loop {
+        // the lock is released as soon as a job is received
+        let job = match receiver.lock() {
+            Ok(receiver) => receiver.recv(),
+            Err(_) => return,
+        };
+        // the channel is closed when the pool is dropped
+        let Ok(job) = job else { return };
+        stats.queued.fetch_sub(1, Ordering::Relaxed);
+        stats.busy.fetch_add(1, Ordering::Relaxed);
+        // a panicking job doesn't kill the worker
+        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+            log_error!("a job of the thread pool panicked!");
+        }
+        stats.busy.fetch_sub(1, Ordering::Relaxed);
+        stats.completed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // closing the channel stops the workers after the queued jobs
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // job that tells when it started and waits for `release`
+    fn blocking_job(started: &mpsc::Sender<()>, release: &Arc<Mutex<Receiver<()>>>) -> Job {
+        let started = started.clone();
+        let release = Arc::clone(release);
+        Box::new(move || {
+            started.send(()).unwrap();
+            let _ = release.lock().unwrap().recv();
+        })
+    }
+
+    #[test]
+    fn try_execute_gives_the_job_back_when_the_queue_is_full() {
+        let pool = ThreadPool::new(1, 1);
+        let stats = pool.stats();
+        let (started, started_rx) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+
+        assert!(pool.try_execute(blocking_job(&started, &release_rx)).is_ok());
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(pool.try_execute(blocking_job(&started, &release_rx)).is_ok());
+        assert!(pool.try_execute(blocking_job(&started, &release_rx)).is_err());
+        let snapshot = stats.snapshot();
+        assert_eq!((snapshot.workers, snapshot.busy, snapshot.queued, snapshot.rejected), (1, 1, 1, 1));
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        drop(pool);
+        let snapshot = stats.snapshot();
+        assert_eq!((snapshot.busy, snapshot.queued, snapshot.completed, snapshot.rejected), (0, 0, 2, 1));
+    }
+
+    #[test]
+    fn a_panicking_job_doesnt_stop_the_worker() {
+        let pool = ThreadPool::new(1, 2);
+        let stats = pool.stats();
+        let (done, done_rx) = mpsc::channel();
+        pool.execute(Box::new(|| panic!("the job failed")));
+        pool.execute(Box::new(move || done.send(()).unwrap()));
+        done_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(pool);
+        assert_eq!(stats.snapshot().completed, 2);
+    }
+}
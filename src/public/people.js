@@ -7,27 +7,26 @@ const deleteButton = document.getElementById("delete-person-button");
 
 async function createPerson(name, age) {
   try {
-    const request = await fetch("/create-people", {
+    const request = await fetch("/people", {
       method: "POST",
       headers: { "Content-Type": "application/json" },
       body: JSON.stringify({ name: name, age: age })
     });
 
-    if (!request.ok) throw new Error("an error ocurred to request!");
-    return await request.text();
+    const body = await request.json();
+    // the errors are problem details, like a 409 with the id of the people that has the name
+    if (request.status === 409) return `${body.detail}, by the people ${body.existing_id}`;
+    if (!request.ok) return body.errors ? body.errors.map(e => `${e.field} ${e.message}`).join("\n") : body.detail;
+    return `People was created with the id ${body.id}!`;
   } catch (err) { console.error(err); }
 }
 
 async function deletePerson(id) {
   try {
-    const request = await fetch("/delete-people", {
-      method: "DELETE",
-      headers: { "Content-Type": "application/json" },
-      body: JSON.stringify({ id: id })
-    });
+    const request = await fetch(`/people/${id}`, { method: "DELETE" });
 
     if (!request.ok) throw new Error("an error ocurred to request!");
-    return await request.text();
+    return "people was deleted!";
   } catch (err) { console.error(err); }
 }
 
@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use crate::response_message::StatusCode;
+use crate::chunked::parse_chunk_size;
+use crate::headers::{self, HeaderMap};
+
+// size of each read done in the socket
+const READ_CHUNK_SIZE: usize = 4096;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestMethod {
+    GET,
+    HEAD,
+    POST,
+    PUT,
+    PATCH,
+    DELETE,
+    OPTIONS,
+    Invalid,
+}
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: RequestMethod,
+    pub target: String,
+    pub version: String,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    // trailer fields sent after a chunked body
+    pub trailers: HeaderMap,
+    // parameters captured by the matched route, like the id of /people/{id}
+    pub params: HashMap<String, String>,
+}
+
+// limits applied while reading a request of the socket
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    pub max_header_bytes: usize,
+    pub max_body_bytes: usize,
+}
+
+// time the client has to send each part of a request, a client that doesn't send the
+// headers or the body in time is answered with 408
+#[derive(Debug, Clone, Copy)]
+pub struct RequestTimeouts {
+    // wait for the first byte of a request, the connection is closed silently after it
+    pub idle: Duration,
+    // wait for each read of the socket once the request started
+    pub read: Duration,
+    pub header: Duration,
+    pub body: Duration,
+}
+
+// streams whose reads can be limited in time, like a TcpStream
+pub trait ReadTimeout: Read {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    // the client closed the connection before sending any byte
+    Closed,
+    BadRequest(String),
+    HeadersTooLarge,
+    BodyTooLarge,
+    UnsupportedTransferEncoding,
+    // the client didn't send the request in time
+    Timeout,
+    Io(io::Error),
+}
+
+// reads requests of a stream, keeping the bytes that were read after the end of a request
+pub struct RequestReader<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    timeouts: RequestTimeouts,
+    // end of the time to read the current part of the request
+    deadline: Option<Instant>,
+}
+
+impl RequestMethod {
+    pub fn parse_request_method(str_method: &str) -> Self {
+        match str_method {
+            "GET" => Self::GET,
+            "HEAD" => Self::HEAD,
+            "POST" => Self::POST,
+            "PUT" => Self::PUT,
+            "PATCH" => Self::PATCH,
+            "DELETE" => Self::DELETE,
+            "OPTIONS" => Self::OPTIONS,
+            _ => Self::Invalid,
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::GET => "GET",
+            Self::HEAD => "HEAD",
+            Self::POST => "POST",
+            Self::PUT => "PUT",
+            Self::PATCH => "PATCH",
+            Self::DELETE => "DELETE",
+            Self::OPTIONS => "OPTIONS",
+            Self::Invalid => "INVALID",
+        }
+    }
+}
+
+impl Request {
+    // get a header value by name, ignoring the case of the name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+    // path of the request target, without the query string
+    // path of the target without the query. an absolute-form target, like
+    // http://example.com/people?page=2, is reduced to its path
+    pub fn path(&self) -> &str {
+        let path = self.target.split('?').next().unwrap_or("");
+        match path.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") => {
+                rest.find('/').map_or("/", |start| &rest[start..])
+            },
+            _ => path,
+        }
+    }
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|v| v.as_str())
+    }
+    pub fn query(&self) -> Option<&str> {
+        self.target.split_once('?').map(|(_, q)| q)
+    }
+    // check if the client wants the connection to stay open after the response,
+    // HTTP/1.1 keeps it open unless "close" is sent, HTTP/1.0 only when "keep-alive" is sent
+    pub fn wants_keep_alive(&self) -> bool {
+        if self.version == "HTTP/1.0" {
+            self.headers.has_token("connection", "keep-alive")
+        } else {
+            !self.headers.has_token("connection", "close")
+        }
+    }
+    pub fn body_str(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits { max_header_bytes: 8 * 1024, max_body_bytes: 1024 * 1024 }
+    }
+}
+
+impl Default for RequestTimeouts {
+    fn default() -> Self {
+        RequestTimeouts {
+            idle: Duration::from_secs(5),
+            read: Duration::from_secs(5),
+            header: Duration::from_secs(10),
+            body: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReadTimeout for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+impl ReadTimeout for &TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl ParseError {
+    // status code to answer the client, `None` when no response can be sent
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            ParseError::Closed | ParseError::Io(_) => None,
+            ParseError::BadRequest(_) => Some(StatusCode::BadRequest),
+            ParseError::HeadersTooLarge => Some(StatusCode::RequestHeaderFieldsTooLarge),
+            ParseError::BodyTooLarge => Some(StatusCode::PayloadTooLarge),
+            ParseError::UnsupportedTransferEncoding => Some(StatusCode::NotImplemented),
+            ParseError::Timeout => Some(StatusCode::RequestTimeout),
+        }
+    }
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Closed => write!(f, "connection closed"),
+            ParseError::BadRequest(msg) => write!(f, "bad request: {}", msg),
+            ParseError::HeadersTooLarge => write!(f, "request headers too large"),
+            ParseError::BodyTooLarge => write!(f, "request body too large"),
+            ParseError::UnsupportedTransferEncoding => write!(f, "unsupported transfer-encoding"),
+            ParseError::Timeout => write!(f, "the request wasn't received in time"),
+            ParseError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+impl fmt::Display for RequestMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+// decode the %XX sequences of a path or query, `None` when a sequence is malformed
+pub fn percent_decode(input: &str) -> Option<Vec<u8>> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
+// socket timeouts are reported as WouldBlock on unix and TimedOut on windows
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+fn bad_request(msg: &str) -> ParseError {
+    ParseError::BadRequest(msg.to_string())
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_request_line(line: &str) -> Result<(RequestMethod, String, String), ParseError> {
+    // METHOD SP TARGET SP VERSION
+    let parts: Vec<&str> = line.split(' ').collect();
+    if parts.len() != 3 {
+        return Err(bad_request("malformed request line"));
+    }
+    let (method, target, version) = (parts[0], parts[1], parts[2]);
+    // the method is a token, like the header names
+    if !headers::is_valid_name(method) {
+        return Err(bad_request("invalid method"));
+    }
+    if target.is_empty() || target.bytes().any(|b| b.is_ascii_control()) {
+        return Err(bad_request("invalid request target"));
+    }
+    if version != "HTTP/1.1" && version != "HTTP/1.0" {
+        return Err(bad_request("unsupported http version"));
+    }
+    Ok((RequestMethod::parse_request_method(method), target.to_string(), version.to_string()))
+}
+
+fn parse_headers<'a>(lines: impl Iterator<Item = &'a str>) -> Result<HeaderMap, ParseError> {
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            // obsolete line folding is not accepted (RFC 9112 section 5.2)
+            return Err(bad_request("obsolete header line folding"));
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| bad_request("malformed header line"))?;
+        let value = value.trim_matches(|c| c == ' ' || c == '\t');
+        headers.append(name, value).map_err(|err| ParseError::BadRequest(err.to_string()))?;
+    }
+    Ok(headers)
+}
+
+// HTTP/1.1 requests must have a Host header and no request can have more than one
+// (RFC 9112 section 3.2), both are answered with 400
+fn check_host(headers: &HeaderMap, version: &str) -> Result<(), ParseError> {
+    match headers.get_all("host").count() {
+        0 if version == "HTTP/1.1" => Err(bad_request("missing host header")),
+        0 | 1 => Ok(()),
+        _ => Err(bad_request("more than one host header")),
+    }
+}
+
+fn parse_content_length(headers: &HeaderMap) -> Result<usize, ParseError> {
+    if !headers.contains("content-length") {
+        return Ok(0);
+    }
+    // repeated content-length headers are only accepted when all of them are equal
+    let mut values = headers.get_all("content-length").flat_map(|v| v.split(',')).map(|v| v.trim());
+    let first = values.next().unwrap_or("");
+    if values.any(|v| v != first) {
+        return Err(bad_request("conflicting content-length headers"));
+    }
+    if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(bad_request("invalid content-length"));
+    }
+    first.parse::<usize>().map_err(|_| ParseError::BodyTooLarge)
+}
+
+// "chunked" is the only transfer coding supported, any other coding is answered with 501
+fn is_chunked(headers: &HeaderMap) -> Result<bool, ParseError> {
+    if !headers.contains("transfer-encoding") {
+        return Ok(false);
+    }
+    if headers.contains("content-length") {
+        return Err(bad_request("both transfer-encoding and content-length were sent"));
+    }
+    let codings: Vec<String> = headers.get_all("transfer-encoding")
+        .flat_map(|v| v.split(','))
+        .map(|c| c.trim().to_ascii_lowercase())
+        .collect();
+    if codings.len() != 1 || codings[0] != "chunked" {
+        return Err(ParseError::UnsupportedTransferEncoding);
+    }
+    Ok(true)
+}
+
+impl<R: ReadTimeout> RequestReader<R> {
+    pub fn new(inner: R) -> Self {
+        RequestReader { inner, buffer: Vec::new(), timeouts: RequestTimeouts::default(), deadline: None }
+    }
+
+    // read more bytes of the stream into the buffer, returns the number of bytes read.
+    // before the request starts the read waits the idle timeout, then the read
+    // timeout limited by the deadline of the current part
+    fn fill_buffer(&mut self) -> Result<usize, ParseError> {
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(ParseError::Timeout);
+                }
+                self.timeouts.read.min(remaining)
+            },
+            None => self.timeouts.idle,
+        };
+        self.inner.set_read_timeout(Some(timeout))?;
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        let n = loop {
+            match self.inner.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) if is_timeout(&err) && self.deadline.is_some() => return Err(ParseError::Timeout),
+                Err(err) => return Err(ParseError::Io(err)),
+            }
+        };
+        self.buffer.extend_from_slice(&chunk[..n]);
+        Ok(n)
+    }
+
+    // a request was already received or arrives within `timeout`,
+    // used on shutdown to serve only the requests that were sent
+    pub fn has_request(&mut self, timeout: Duration) -> bool {
+        if !self.buffer.is_empty() {
+            return true;
+        }
+        let idle = self.timeouts.idle;
+        self.timeouts.idle = timeout;
+        self.deadline = None;
+        let received = matches!(self.fill_buffer(), Ok(n) if n > 0);
+        self.timeouts.idle = idle;
+        received
+    }
+
+    pub fn read_request(&mut self, limits: &RequestLimits, timeouts: &RequestTimeouts) -> Result<Request, ParseError> {
+        self.read_request_with(limits, timeouts, || {})
+    }
+
+    // same as `read_request`, calling `on_start` when the first byte of the request is received
+    pub fn read_request_with(&mut self, limits: &RequestLimits, timeouts: &RequestTimeouts, mut on_start: impl FnMut()) -> Result<Request, ParseError> {
+        self.timeouts = *timeouts;
+        // the time for the headers starts with the first byte of the request
+        self.deadline = None;
+        if !self.buffer.is_empty() {
+            self.deadline = Some(Instant::now() + timeouts.header);
+            on_start();
+        }
+        // find the blank line that ends the headers, reading as many times as needed
+        let head_end = loop {
+            // empty lines before the request line are ignored (RFC 9112 section 2.2)
+            let leading = self.buffer.iter().take_while(|b| **b == b'\r' || **b == b'\n').count();
+            self.buffer.drain(..leading);
+            if let Some(pos) = find_bytes(&self.buffer, b"\r\n\r\n") {
+                if pos > limits.max_header_bytes {
+                    return Err(ParseError::HeadersTooLarge);
+                }
+                break pos;
+            }
+            if self.buffer.len() > limits.max_header_bytes {
+                return Err(ParseError::HeadersTooLarge);
+            }
+            if self.fill_buffer()? == 0 {
+                if self.buffer.is_empty() {
+                    return Err(ParseError::Closed);
+                }
+                return Err(bad_request("connection closed in the middle of the headers"));
+            }
+            if self.deadline.is_none() {
+                self.deadline = Some(Instant::now() + timeouts.header);
+                on_start();
+            }
+        };
+
+        let head = std::str::from_utf8(&self.buffer[..head_end])
+            .map_err(|_| bad_request("request head is not valid utf-8"))?;
+        let mut lines = head.split("\r\n");
+        let (method, target, version) = parse_request_line(lines.next().unwrap_or(""))?;
+        let headers = parse_headers(lines)?;
+        check_host(&headers, &version)?;
+        let chunked = is_chunked(&headers)?;
+        let content_length = parse_content_length(&headers)?;
+        if content_length > limits.max_body_bytes {
+            return Err(ParseError::BodyTooLarge);
+        }
+        self.buffer.drain(..head_end + 4);
+        self.deadline = Some(Instant::now() + timeouts.body);
+
+        let mut trailers = HeaderMap::new();
+        let body = if chunked {
+            self.read_chunked_body(limits, &mut trailers)?
+        } else {
+            // read exactly content-length bytes of body
+            self.read_exact_bytes(content_length)?
+        };
+
+        Ok(Request { method, target, version, headers, body, trailers, params: HashMap::new() })
+    }
+
+    fn read_exact_bytes(&mut self, len: usize) -> Result<Vec<u8>, ParseError> {
+        while self.buffer.len() < len {
+            if self.fill_buffer()? == 0 {
+                return Err(bad_request("connection closed in the middle of the body"));
+            }
+        }
+        Ok(self.buffer.drain(..len).collect())
+    }
+
+    // read a line ended by CRLF, without the CRLF
+    fn read_line(&mut self, max_len: usize) -> Result<String, ParseError> {
+        let end = loop {
+            if let Some(pos) = find_bytes(&self.buffer, b"\r\n") {
+                break pos;
+            }
+            if self.buffer.len() > max_len {
+                return Err(bad_request("line too long in the chunked body"));
+            }
+            if self.fill_buffer()? == 0 {
+                return Err(bad_request("connection closed in the middle of the body"));
+            }
+        };
+        let line = String::from_utf8(self.buffer[..end].to_vec())
+            .map_err(|_| bad_request("chunked body line is not valid utf-8"))?;
+        self.buffer.drain(..end + 2);
+        Ok(line)
+    }
+
+    fn read_chunked_body(&mut self, limits: &RequestLimits, trailers: &mut HeaderMap) -> Result<Vec<u8>, ParseError> {
+        let mut body: Vec<u8> = Vec::new();
+        loop {
+            let size_line = self.read_line(limits.max_header_bytes)?;
+            let size = parse_chunk_size(&size_line).ok_or_else(|| bad_request("invalid chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            // the body is never larger than the limit, so the subtraction can't overflow
+            if size > limits.max_body_bytes - body.len() {
+                return Err(ParseError::BodyTooLarge);
+            }
+            let chunk = self.read_exact_bytes(size)?;
+            body.extend_from_slice(&chunk);
+            // the CRLF can arrive in a later read than the data
+            if self.read_exact_bytes(2)? != b"\r\n" {
+                return Err(bad_request("chunk data is not followed by CRLF"));
+            }
+        }
+
+        // trailer section, ended by an empty line
+        let mut trailer_lines: Vec<String> = Vec::new();
+        let mut trailer_bytes = 0;
+        loop {
+            let line = self.read_line(limits.max_header_bytes)?;
+            if line.is_empty() {
+                break;
+            }
+            trailer_bytes += line.len() + 2;
+            if trailer_bytes > limits.max_header_bytes {
+                return Err(ParseError::HeadersTooLarge);
+            }
+            trailer_lines.push(line);
+        }
+        *trailers = parse_headers(trailer_lines.iter().map(|l| l.as_str()))?;
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // stream that returns each piece in a separate read, like a client sending
+    // the request in several tcp segments, and then the end of the stream
+    struct Pieces(VecDeque<Vec<u8>>);
+
+    impl Read for Pieces {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some(mut piece) = self.0.pop_front() else { return Ok(0) };
+            let n = piece.len().min(buf.len());
+            buf[..n].copy_from_slice(&piece[..n]);
+            if n < piece.len() {
+                self.0.push_front(piece.split_off(n));
+            }
+            Ok(n)
+        }
+    }
+    impl ReadTimeout for Pieces {
+        fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn reader(pieces: &[&str]) -> RequestReader<Pieces> {
+        RequestReader::new(Pieces(pieces.iter().map(|p| p.as_bytes().to_vec()).collect()))
+    }
+    fn read(pieces: &[&str]) -> Result<Request, ParseError> {
+        reader(pieces).read_request(&RequestLimits::default(), &RequestTimeouts::default())
+    }
+    fn status(result: Result<Request, ParseError>) -> Option<StatusCode> {
+        result.err().and_then(|err| err.status_code())
+    }
+
+    #[test]
+    fn content_length_body_is_read_across_reads() {
+        let request = read(&["POST /people HT", "TP/1.1\r\nHost: test\r\nContent-Le", "ngth: 11\r\n\r", "\nhello", " world"]).unwrap();
+        assert_eq!(request.method, RequestMethod::POST);
+        assert_eq!(request.target, "/people");
+        assert_eq!(request.header("content-length"), Some("11"));
+        assert_eq!(request.body, b"hello world");
+    }
+
+    #[test]
+    fn request_start_is_reported_with_the_first_byte() {
+        let (limits, timeouts) = (RequestLimits::default(), RequestTimeouts::default());
+        let mut started = 0;
+        reader(&["G", "ET / HTTP/1.1\r\nHost: test\r\n", "\r\n"]).read_request_with(&limits, &timeouts, || started += 1).unwrap();
+        assert_eq!(started, 1);
+        let closed = reader(&[]).read_request_with(&limits, &timeouts, || started += 1);
+        assert!(matches!(closed, Err(ParseError::Closed)));
+        assert_eq!(started, 1);
+    }
+
+    #[test]
+    fn pipelined_requests_are_read_one_by_one() {
+        let mut reader = reader(&["GET /a HTTP/1.1\r\nHost: test\r\n\r\nPOST /b HTTP/1.1\r\nHost: test\r\nContent-Length: 2\r\n\r\nokGET /c HTTP/1.1\r\nHost: test\r\n", "\r\n"]);
+        let (limits, timeouts) = (RequestLimits::default(), RequestTimeouts::default());
+        assert_eq!(reader.read_request(&limits, &timeouts).unwrap().target, "/a");
+        let request = reader.read_request(&limits, &timeouts).unwrap();
+        assert_eq!((request.target.as_str(), request.body.as_slice()), ("/b", b"ok".as_slice()));
+        assert_eq!(reader.read_request(&limits, &timeouts).unwrap().target, "/c");
+        assert!(matches!(reader.read_request(&limits, &timeouts), Err(ParseError::Closed)));
+    }
+
+    #[test]
+    fn oversized_requests_are_rejected() {
+        let limits = RequestLimits { max_header_bytes: 64, max_body_bytes: 8 };
+        let long_header = format!("GET / HTTP/1.1\r\nHost: test\r\nX-Long: {}\r\n\r\n", "a".repeat(100));
+        let result = reader(&[&long_header]).read_request(&limits, &RequestTimeouts::default());
+        assert_eq!(status(result), Some(StatusCode::RequestHeaderFieldsTooLarge));
+        let result = reader(&["POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 9\r\n\r\n123456789"]).read_request(&limits, &RequestTimeouts::default());
+        assert_eq!(status(result), Some(StatusCode::PayloadTooLarge));
+    }
+
+    #[test]
+    fn ambiguous_body_lengths_are_rejected() {
+        let conflicting = read(&["POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\nhello!"]);
+        assert_eq!(status(conflicting), Some(StatusCode::BadRequest));
+        let conflicting = read(&["POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5, 6\r\n\r\nhello!"]);
+        assert_eq!(status(conflicting), Some(StatusCode::BadRequest));
+        let both = read(&["POST / HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n5\r\nhello\r\n0\r\n\r\n"]);
+        assert_eq!(status(both), Some(StatusCode::BadRequest));
+    }
+
+    #[test]
+    fn chunked_body_is_read_across_reads() {
+        let request = read(&["POST / HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n5\r", "\nhel", "lo\r", "\n6\r\n world\r\n0\r\nX-Sum: 1\r", "\n\r\n"]).unwrap();
+        assert_eq!(request.body, b"hello world");
+        assert_eq!(request.trailers.get("x-sum"), Some("1"));
+
+        let missing_crlf = read(&["POST / HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhelloXY0\r\n\r\n"]);
+        assert_eq!(status(missing_crlf), Some(StatusCode::BadRequest));
+    }
+
+    #[test]
+    fn chunk_sizes_beyond_the_limit_are_rejected() {
+        let huge = read(&["POST / HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nFFFFFFFFFFFFFFFF\r\n"]);
+        assert_eq!(status(huge), Some(StatusCode::PayloadTooLarge));
+    }
+
+    #[test]
+    fn host_is_required_once_in_http11() {
+        assert_eq!(status(read(&["GET / HTTP/1.1\r\n\r\n"])), Some(StatusCode::BadRequest));
+        assert_eq!(status(read(&["GET / HTTP/1.1\r\nHost: a\r\nhost: b\r\n\r\n"])), Some(StatusCode::BadRequest));
+        assert_eq!(status(read(&["GET / HTTP/1.0\r\nHost: a\r\nHost: a\r\n\r\n"])), Some(StatusCode::BadRequest));
+        assert!(read(&["GET / HTTP/1.0\r\n\r\n"]).is_ok());
+        assert!(read(&["GET / HTTP/1.1\r\nHOST: a\r\n\r\n"]).is_ok());
+    }
+
+    #[test]
+    fn absolute_form_targets_are_reduced_to_their_path() {
+        let request = read(&["GET http://example.com:8080/people/1?full=true HTTP/1.1\r\nHost: example.com:8080\r\n\r\n"]).unwrap();
+        assert_eq!((request.path(), request.query()), ("/people/1", Some("full=true")));
+        let path = |target: &str| read(&[&format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", target)]).unwrap().path().to_string();
+        assert_eq!(path("HTTPS://example.com"), "/");
+        assert_eq!(path("http://example.com?page=2"), "/");
+        assert_eq!(path("/redirect/http://example.com/a"), "/redirect/http://example.com/a");
+        assert_eq!(path("/people?next=http://example.com/a"), "/people");
+    }
+}
@@ -0,0 +1,354 @@
+use std::fs::File;
+use std::path::Path;
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use crate::response_message::StatusCode;
+use crate::chunked::ChunkedWriter;
+use crate::static_files::mime_type;
+use crate::headers::{HeaderMap, HeaderError};
+
+const SERVER_NAME: &str = concat!("http-server/", env!("CARGO_PKG_VERSION"));
+
+pub enum Body {
+    Full(Vec<u8>),
+    // file sent from the disk, the length is the `Content-Length` of the response
+    File(File, u64),
+    // body read until the end and sent with the chunked transfer coding
+    Stream(Box<dyn Read + Send>),
+}
+
+// the `Content-Length`, `Date` and `Server` headers are filled when the response is sent
+pub struct Response {
+    pub status_code: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Body,
+}
+
+// html body sent with the text/html content type
+pub struct Html<T>(pub T);
+
+// values that handlers can return, the server converts them and writes the response
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+// an invalid header set in the builder turns the response in a 500
+pub struct ResponseBuilder {
+    status_code: StatusCode,
+    headers: HeaderMap,
+    error: Option<HeaderError>,
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(body: Vec<u8>) -> Self {
+        Body::Full(body)
+    }
+}
+impl From<&[u8]> for Body {
+    fn from(body: &[u8]) -> Self {
+        Body::Full(body.to_vec())
+    }
+}
+impl From<String> for Body {
+    fn from(body: String) -> Self {
+        Body::Full(body.into_bytes())
+    }
+}
+impl From<&str> for Body {
+    fn from(body: &str) -> Self {
+        Body::Full(body.as_bytes().to_vec())
+    }
+}
+
+impl ResponseBuilder {
+    pub fn status(mut self, status_code: StatusCode) -> Self {
+        self.status_code = status_code;
+        self
+    }
+    // set a header, replacing the values of the headers with the same name
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if let Err(err) = self.headers.insert(name, value) {
+            self.error.get_or_insert(err);
+        }
+        self
+    }
+    // add a header without replacing the values set before, like for Set-Cookie
+    pub fn append_header(mut self, name: &str, value: &str) -> Self {
+        if let Err(err) = self.headers.append(name, value) {
+            self.error.get_or_insert(err);
+        }
+        self
+    }
+    pub fn content_type(self, content_type: &str) -> Self {
+        self.header("Content-Type", content_type)
+    }
+    // the content type is only set when it wasn't set before
+    fn default_content_type(self, content_type: &str) -> Self {
+        if self.headers.contains("Content-Type") { self } else { self.content_type(content_type) }
+    }
+
+    pub fn body(self, body: impl Into<Body>) -> Response {
+        if let Some(err) = self.error {
+            log_error!("an error ocurred to build the response!\n{}", err);
+            return Response::builder().status(StatusCode::InternalServerError).text("an error ocurred to build the response!");
+        }
+        Response { status_code: self.status_code, headers: self.headers, body: body.into() }
+    }
+    pub fn empty(self) -> Response {
+        self.body(Vec::new())
+    }
+    pub fn text(self, text: impl Into<String>) -> Response {
+        self.default_content_type("text/plain; charset=utf-8").body(text.into())
+    }
+    pub fn html(self, html: impl Into<String>) -> Response {
+        self.default_content_type("text/html; charset=utf-8").body(html.into())
+    }
+    pub fn json<T: Serialize + ?Sized>(self, value: &T) -> Response {
+        match serde_json::to_vec(value) {
+            Ok(json) => self.default_content_type("application/json").body(json),
+            Err(err) => {
+                log_error!("an error ocurred to serialize the json response!\n{}", err);
+                Response::builder().status(StatusCode::InternalServerError).text("an error ocurred to build the response!")
+            }
+        }
+    }
+    // body read from the file while it is sent, with the content type of its extension
+    pub fn file(self, path: impl AsRef<Path>) -> io::Result<Response> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(self.default_content_type(mime_type(path)).body(Body::File(file, len)))
+    }
+    // body with unknown length, sent with the chunked transfer coding
+    pub fn stream(self, reader: impl Read + Send + 'static) -> Response {
+        self.body(Body::Stream(Box::new(reader)))
+    }
+}
+
+impl Response {
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder { status_code: StatusCode::Success, headers: HeaderMap::new(), error: None }
+    }
+    // 302 response sending the client to `location`
+    pub fn redirect(location: &str) -> Self {
+        Response::builder().status(StatusCode::Found).header("Location", location).empty()
+    }
+    pub fn no_content() -> Self {
+        Response::builder().status(StatusCode::NoContent).empty()
+    }
+    pub fn is_stream(&self) -> bool {
+        matches!(self.body, Body::Stream(_))
+    }
+    // responses with these status codes never have a body (RFC 9110 section 6.4.1)
+    fn allows_body(&self) -> bool {
+        !(self.status_code.is_informational()
+            || self.status_code == StatusCode::NoContent
+            || self.status_code == StatusCode::NotModified)
+    }
+    // set a header generated by the server, these values are always valid
+    fn set_header(&mut self, name: &str, value: String) {
+        let _ = self.headers.insert(name, &value);
+    }
+    fn set_default_header(&mut self, name: &str, value: String) {
+        if !self.headers.contains(name) {
+            self.set_header(name, value);
+        }
+    }
+    fn parse_head(&self) -> String {
+        let mut response = String::new();
+        response.push_str(&format!("HTTP/1.1 {}\r\n", self.status_code));
+        for (k, v) in self.headers.iter() {
+            response.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        response.push_str("\r\n");
+        response
+    }
+    // set the `Connection` header of the response, with the `Keep-Alive` parameters when it stays open
+    pub fn set_keep_alive(&mut self, keep_alive: Option<String>) {
+        match keep_alive {
+            Some(params) => {
+                self.set_header("Connection", "keep-alive".to_string());
+                self.set_header("Keep-Alive", params);
+            },
+            None => self.set_header("Connection", "close".to_string()),
+        }
+    }
+    // write the response in the stream. streamed bodies are sent with the chunked
+    // transfer coding when `chunked` is true, or until the connection is closed otherwise.
+    // with `include_body` false only the status line and headers are sent, used by HEAD requests
+    pub fn write_to<W: Write>(mut self, mut writer: W, chunked: bool, include_body: bool) -> io::Result<()> {
+        self.set_default_header("Date", http_date(SystemTime::now()));
+        self.set_default_header("Server", SERVER_NAME.to_string());
+        let body = std::mem::replace(&mut self.body, Body::Full(Vec::new()));
+        if !self.allows_body() {
+            self.headers.remove("Content-Length");
+            writer.write_all(self.parse_head().as_bytes())?;
+            return writer.flush();
+        }
+        match body {
+            Body::Full(body_content) => {
+                self.set_header("Content-Length", body_content.len().to_string());
+                let mut response = self.parse_head().into_bytes();
+                if include_body {
+                    response.extend_from_slice(&body_content);
+                }
+                writer.write_all(&response)?;
+            },
+            Body::File(file, len) => {
+                self.set_header("Content-Length", len.to_string());
+                writer.write_all(self.parse_head().as_bytes())?;
+                if include_body {
+                    // send exactly the length announced, even if the file grows meanwhile
+                    let sent = io::copy(&mut file.take(len), &mut writer)?;
+                    if sent < len {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the file was truncated while it was sent"));
+                    }
+                }
+            },
+            Body::Stream(mut reader) => {
+                self.headers.remove("Content-Length");
+                if chunked {
+                    self.set_header("Transfer-Encoding", "chunked".to_string());
+                }
+                writer.write_all(self.parse_head().as_bytes())?;
+                if include_body && chunked {
+                    let mut chunked_writer = ChunkedWriter::new(&mut writer);
+                    io::copy(&mut reader, &mut chunked_writer)?;
+                    chunked_writer.finish()?;
+                } else if include_body {
+                    io::copy(&mut reader, &mut writer)?;
+                }
+            },
+        }
+        writer.flush()
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::builder().text(self)
+    }
+}
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        Response::builder().text(self)
+    }
+}
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response {
+        Response::builder().status(self).empty()
+    }
+}
+impl<T: Into<String>> IntoResponse for Html<T> {
+    fn into_response(self) -> Response {
+        Response::builder().html(self.0)
+    }
+}
+// the status code replaces the one of the converted value, like (StatusCode::Created, Json(people))
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> Response {
+        let mut response = self.1.into_response();
+        response.status_code = self.0;
+        response
+    }
+}
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+}
+
+// format a time as an IMF-fixdate, like "Sun, 06 Nov 1994 08:49:37 GMT"
+pub fn http_date(time: SystemTime) -> String {
+    // 1970-01-01 was a thursday
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+
+    // convert the days since 1970-01-01 to a civil date (algorithm by Howard Hinnant)
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize], day, MONTHS[(month - 1) as usize], year,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(response: Response, chunked: bool, include_body: bool) -> String {
+        let mut output = Vec::new();
+        response.write_to(&mut output, chunked, include_body).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn headers_are_sent_in_the_order_they_were_added() {
+        let response = Response::builder()
+            .append_header("Set-Cookie", "a=1")
+            .header("X-First", "1")
+            .append_header("Set-Cookie", "b=2")
+            .text("hello");
+        let output = written(response, true, true);
+        let (head, body) = output.split_once("\r\n\r\n").unwrap();
+        let fields: Vec<&str> = head.lines().skip(1).filter(|line| !line.starts_with("Date") && !line.starts_with("Server")).collect();
+        assert_eq!(fields, ["Set-Cookie: a=1", "X-First: 1", "Set-Cookie: b=2", "Content-Type: text/plain; charset=utf-8", "Content-Length: 5"]);
+        assert_eq!(body, "hello");
+    }
+
+    #[test]
+    fn invalid_headers_are_answered_with_500() {
+        let response = Response::builder().header("Location", "/a\r\nSet-Cookie: admin=1").text("moved");
+        assert_eq!(response.status_code, StatusCode::InternalServerError);
+        assert!(!written(response, true, true).contains("admin"));
+    }
+
+    fn stream() -> Response {
+        // two reads of the body, so it is sent in two chunks
+        Response::builder().content_type("text/plain").header("Content-Length", "11").stream(b"hello ".chain(&b"world"[..]))
+    }
+
+    #[test]
+    fn streamed_bodies_are_sent_in_chunks() {
+        let output = written(stream(), true, true);
+        let (head, body) = output.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("\r\nTransfer-Encoding: chunked"));
+        assert!(!head.contains("Content-Length"));
+        assert_eq!(body, "6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n");
+
+        let output = written(stream(), true, false);
+        assert!(output.contains("\r\nTransfer-Encoding: chunked"));
+        assert!(output.ends_with("\r\n\r\n") && !output.contains("hello"));
+    }
+
+    #[test]
+    fn streamed_bodies_are_sent_raw_to_http10_clients() {
+        // without chunks the end of the body is the end of the connection
+        let output = written(stream(), false, true);
+        let (head, body) = output.split_once("\r\n\r\n").unwrap();
+        assert!(!head.contains("Transfer-Encoding") && !head.contains("Content-Length"));
+        assert_eq!(body, "hello world");
+    }
+}
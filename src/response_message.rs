@@ -1,63 +1,125 @@
 use std::fmt;
-use std::io::{Write};
-use std::net::TcpStream;
-use std::collections::{HashMap};
 
-#[derive(Copy, Clone)]
-pub enum StatusCode {
-    Success,
-    Created,
-    BadRequest,
-    NotFound,
-    InternalServerError,
-}
+// defines the StatusCode enum with the code and reason phrase of each variant
+macro_rules! status_codes {
+    ($($name:ident => ($code:literal, $reason:literal),)+) => {
+        // status codes registered by IANA, other codes can be used with `Custom`
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum StatusCode {
+            $($name,)+
+            Custom(u16),
+        }
 
-pub struct ResponseMessage {
-    pub content: String,
+        impl StatusCode {
+            pub fn as_u16(&self) -> u16 {
+                match self {
+                    $(StatusCode::$name => $code,)+
+                    StatusCode::Custom(code) => *code,
+                }
+            }
+            // status code of the number, `None` when it isn't a 3 digit code of the classes 1xx to 5xx
+            pub fn from_u16(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(StatusCode::$name),)+
+                    100..=599 => Some(StatusCode::Custom(code)),
+                    _ => None,
+                }
+            }
+            // canonical reason phrase, empty for the codes that are not registered
+            pub fn reason_phrase(&self) -> &'static str {
+                match self {
+                    $(StatusCode::$name => $reason,)+
+                    StatusCode::Custom(_) => "",
+                }
+            }
+        }
+    };
 }
 
-impl fmt::Display for StatusCode {
-  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-      match self {
-        StatusCode::Success => write!(f, "200 OK"),
-        StatusCode::Created => write!(f, "201 Created"),
-        StatusCode::BadRequest => write!(f, "400 Bad Request"),
-        StatusCode::NotFound => write!(f, "404 Not Found"),
-        StatusCode::InternalServerError => write!(f, "500 Internal Server Error"),
-      }
-  }
-}
-impl fmt::Display for ResponseMessage {
-  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-      write!(f, "Message: {}", self.content)
-  }
+status_codes! {
+    Continue => (100, "Continue"),
+    SwitchingProtocols => (101, "Switching Protocols"),
+    Processing => (102, "Processing"),
+    EarlyHints => (103, "Early Hints"),
+    Success => (200, "OK"),
+    Created => (201, "Created"),
+    Accepted => (202, "Accepted"),
+    NonAuthoritativeInformation => (203, "Non-Authoritative Information"),
+    NoContent => (204, "No Content"),
+    ResetContent => (205, "Reset Content"),
+    PartialContent => (206, "Partial Content"),
+    MultiStatus => (207, "Multi-Status"),
+    AlreadyReported => (208, "Already Reported"),
+    ImUsed => (226, "IM Used"),
+    MultipleChoices => (300, "Multiple Choices"),
+    MovedPermanently => (301, "Moved Permanently"),
+    Found => (302, "Found"),
+    SeeOther => (303, "See Other"),
+    NotModified => (304, "Not Modified"),
+    UseProxy => (305, "Use Proxy"),
+    TemporaryRedirect => (307, "Temporary Redirect"),
+    PermanentRedirect => (308, "Permanent Redirect"),
+    BadRequest => (400, "Bad Request"),
+    Unauthorized => (401, "Unauthorized"),
+    PaymentRequired => (402, "Payment Required"),
+    Forbidden => (403, "Forbidden"),
+    NotFound => (404, "Not Found"),
+    MethodNotAllowed => (405, "Method Not Allowed"),
+    NotAcceptable => (406, "Not Acceptable"),
+    ProxyAuthenticationRequired => (407, "Proxy Authentication Required"),
+    RequestTimeout => (408, "Request Timeout"),
+    Conflict => (409, "Conflict"),
+    Gone => (410, "Gone"),
+    LengthRequired => (411, "Length Required"),
+    PreconditionFailed => (412, "Precondition Failed"),
+    PayloadTooLarge => (413, "Content Too Large"),
+    UriTooLong => (414, "URI Too Long"),
+    UnsupportedMediaType => (415, "Unsupported Media Type"),
+    RangeNotSatisfiable => (416, "Range Not Satisfiable"),
+    ExpectationFailed => (417, "Expectation Failed"),
+    MisdirectedRequest => (421, "Misdirected Request"),
+    UnprocessableEntity => (422, "Unprocessable Content"),
+    Locked => (423, "Locked"),
+    FailedDependency => (424, "Failed Dependency"),
+    TooEarly => (425, "Too Early"),
+    UpgradeRequired => (426, "Upgrade Required"),
+    PreconditionRequired => (428, "Precondition Required"),
+    TooManyRequests => (429, "Too Many Requests"),
+    RequestHeaderFieldsTooLarge => (431, "Request Header Fields Too Large"),
+    UnavailableForLegalReasons => (451, "Unavailable For Legal Reasons"),
+    InternalServerError => (500, "Internal Server Error"),
+    NotImplemented => (501, "Not Implemented"),
+    BadGateway => (502, "Bad Gateway"),
+    ServiceUnavailable => (503, "Service Unavailable"),
+    GatewayTimeout => (504, "Gateway Timeout"),
+    HttpVersionNotSupported => (505, "HTTP Version Not Supported"),
+    VariantAlsoNegotiates => (506, "Variant Also Negotiates"),
+    InsufficientStorage => (507, "Insufficient Storage"),
+    LoopDetected => (508, "Loop Detected"),
+    NotExtended => (510, "Not Extended"),
+    NetworkAuthenticationRequired => (511, "Network Authentication Required"),
 }
 
-fn header_format(status_code: StatusCode, content_type: &str, content_error: &str) -> String {
-    format!(
-        "HTTP/1.1 {}\r\n\
-    Content-Type: {}\r\n\
-    Content-Length: {}\r\n\
-    Connection: close\r\n\
-    \r\n\
-    {}", status_code, content_type, content_error.len(), content_error)
-}
-pub fn create_response_header(content_type: &str, body_content: &str) -> HashMap<String, String> {
-    let mut headers_hash: HashMap<String, String> = HashMap::new();
-    headers_hash.insert("Content-Length".to_string(), body_content.len().to_string());
-    headers_hash.insert("Content-Type".to_string(), content_type.to_string());
-    headers_hash
+impl StatusCode {
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
 }
 
-impl ResponseMessage {
-  pub fn badrequest_error(content_error: &str, content_type: &str) -> Self {
-      ResponseMessage { content: header_format(StatusCode::BadRequest, content_type, content_error) }
-  }
-  pub fn not_found_error(content_error: &str, content_type: &str) -> Self {
-      ResponseMessage { content: header_format(StatusCode::NotFound, content_type, content_error) }
+impl fmt::Display for StatusCode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "{} {}", self.as_u16(), self.reason_phrase())
   }
 }
-
-pub fn send_response_error(mut stream: &TcpStream, response_content: String) {
-    stream.write_all(&response_content.as_bytes()).unwrap();
-}
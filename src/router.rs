@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::request::{Request, RequestMethod};
+use crate::response::Response;
+use crate::static_files::StaticFiles;
+use crate::handler::Handler;
+
+// order used to list the methods in the `Allow` header
+const ALLOW_ORDER: [RequestMethod; 7] = [
+    RequestMethod::GET,
+    RequestMethod::HEAD,
+    RequestMethod::POST,
+    RequestMethod::PUT,
+    RequestMethod::PATCH,
+    RequestMethod::DELETE,
+    RequestMethod::OPTIONS,
+];
+
+pub type HandlerFn = Box<dyn Fn(&Request) -> Response + Send + Sync>;
+
+// parts of a route pattern, like /people/{id} or /public/{*path}
+#[derive(Debug)]
+enum Segment {
+    Static(String),
+    // {name} captures a single segment of the path
+    Param(String),
+    // {*name} captures the rest of the path, it must be the last segment
+    Wildcard(String),
+}
+
+struct Route {
+    method: RequestMethod,
+    segments: Vec<Segment>,
+    handler: HandlerFn,
+    // files of a static mount, its wildcard only counts as a match of the path
+    // for the allowed methods when the file exists
+    files: Option<Arc<StaticFiles>>,
+}
+
+// the routes are matched by specificity: in each segment a static match wins
+// over a param, and a param wins over a wildcard; equal routes keep the registration order
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+pub struct RouteMatch<'a> {
+    pub handler: &'a HandlerFn,
+    pub params: HashMap<String, String>,
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let parts: Vec<&str> = split_path(pattern).collect();
+    let mut segments = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        let segment = match part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+            Some(name) if name.starts_with('*') => {
+                if i != parts.len() - 1 {
+                    panic!("the wildcard must be the last segment of the route {}", pattern);
+                }
+                Segment::Wildcard(name[1..].to_string())
+            },
+            Some(name) => Segment::Param(name.to_string()),
+            None => Segment::Static(part.to_string()),
+        };
+        segments.push(segment);
+    }
+    segments
+}
+
+impl Segment {
+    // lower values are more specific
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard(_) => 2,
+        }
+    }
+}
+
+impl Route {
+    fn match_path(&self, path: &str) -> Option<HashMap<String, String>> {
+        let parts: Vec<&str> = split_path(path).collect();
+        let mut params = HashMap::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Static(s) => if parts.get(i) != Some(&s.as_str()) { return None },
+                Segment::Param(name) => {
+                    let part = parts.get(i)?;
+                    params.insert(name.clone(), part.to_string());
+                },
+                Segment::Wildcard(name) => {
+                    params.insert(name.clone(), parts[i..].join("/"));
+                    return Some(params);
+                },
+            }
+        }
+        if parts.len() != self.segments.len() {
+            return None;
+        }
+        Some(params)
+    }
+    fn specificity(&self) -> Vec<u8> {
+        self.segments.iter().map(|s| s.rank()).collect()
+    }
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router { routes: Vec::new() }
+    }
+    pub fn route<H, Args>(mut self, method: RequestMethod, pattern: &str, handler: H) -> Self
+    where H: Handler<Args>, Args: 'static {
+        let handler: HandlerFn = Box::new(move |request| handler.call(request));
+        self.routes.push(Route { method, segments: parse_pattern(pattern), handler, files: None });
+        self
+    }
+    pub fn get<H, Args>(self, pattern: &str, handler: H) -> Self
+    where H: Handler<Args>, Args: 'static {
+        self.route(RequestMethod::GET, pattern, handler)
+    }
+    pub fn post<H, Args>(self, pattern: &str, handler: H) -> Self
+    where H: Handler<Args>, Args: 'static {
+        self.route(RequestMethod::POST, pattern, handler)
+    }
+    pub fn put<H, Args>(self, pattern: &str, handler: H) -> Self
+    where H: Handler<Args>, Args: 'static {
+        self.route(RequestMethod::PUT, pattern, handler)
+    }
+    pub fn patch<H, Args>(self, pattern: &str, handler: H) -> Self
+    where H: Handler<Args>, Args: 'static {
+        self.route(RequestMethod::PATCH, pattern, handler)
+    }
+    pub fn delete<H, Args>(self, pattern: &str, handler: H) -> Self
+    where H: Handler<Args>, Args: 'static {
+        self.route(RequestMethod::DELETE, pattern, handler)
+    }
+    // serve the files of `files` in GET requests to the paths that start with `prefix`
+    pub fn mount_static(self, prefix: &str, files: StaticFiles) -> Self {
+        let pattern = format!("{}/{{*path}}", prefix.trim_end_matches('/'));
+        let files = Arc::new(files);
+        let serving = Arc::clone(&files);
+        let mut router = self.get(&pattern, move |request: &Request| serving.serve(request.param("path").unwrap_or("")));
+        if let Some(route) = router.routes.last_mut() {
+            route.files = Some(files);
+        }
+        router
+    }
+
+    // find the most specific route for the method and path,
+    // HEAD requests use the GET route when no HEAD route was registered
+    pub fn find(&self, method: RequestMethod, path: &str) -> Option<RouteMatch<'_>> {
+        let route = self.find_exact(method, path);
+        if route.is_none() && method == RequestMethod::HEAD {
+            return self.find_exact(RequestMethod::GET, path);
+        }
+        route
+    }
+
+    fn find_exact(&self, method: RequestMethod, path: &str) -> Option<RouteMatch<'_>> {
+        let mut best: Option<(&Route, HashMap<String, String>)> = None;
+        for route in self.routes.iter().filter(|r| r.method == method) {
+            let Some(params) = route.match_path(path) else { continue };
+            let more_specific = match &best {
+                Some((current, _)) => route.specificity() < current.specificity(),
+                None => true,
+            };
+            if more_specific {
+                best = Some((route, params));
+            }
+        }
+        best.map(|(route, params)| RouteMatch { handler: &route.handler, params })
+    }
+
+    // methods accepted by the path, OPTIONS is always included when the path exists
+    // and HEAD is included with GET. a static mount only accepts the paths of its files,
+    // so a prefix like "/" doesn't turn every unknown path into a 405.
+    // the path "*" lists the methods of all routes
+    pub fn allowed_methods(&self, path: &str) -> Vec<RequestMethod> {
+        let matches = |route: &Route| path == "*" || route.match_path(path).is_some_and(|params| match &route.files {
+            Some(files) => files.contains(params.get("path").map_or("", |p| p.as_str())),
+            None => true,
+        });
+        let methods: Vec<RequestMethod> = self.routes.iter()
+            .filter(|r| matches(r))
+            .map(|r| r.method)
+            .collect();
+        if methods.is_empty() {
+            return methods;
+        }
+        ALLOW_ORDER.iter()
+            .filter(|m| match m {
+                RequestMethod::OPTIONS => true,
+                RequestMethod::HEAD => methods.contains(&RequestMethod::HEAD) || methods.contains(&RequestMethod::GET),
+                _ => methods.contains(m),
+            })
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn call(router: &Router, method: RequestMethod, path: &str) -> Option<(String, HashMap<String, String>)> {
+        let route = router.find(method, path)?;
+        let request = Request {
+            method, target: path.to_string(), version: "HTTP/1.1".to_string(), headers: Default::default(),
+            body: Vec::new(), trailers: Default::default(), params: route.params.clone(),
+        };
+        let body = match (route.handler)(&request).body {
+            crate::response::Body::Full(body) => String::from_utf8(body).unwrap(),
+            _ => String::new(),
+        };
+        Some((body, route.params))
+    }
+
+    #[test]
+    fn specific_segments_win_over_params_and_wildcards() {
+        let router = Router::new()
+            .get("/files/{*path}", |_: &Request| "wildcard")
+            .get("/files/{name}", |_: &Request| "param")
+            .get("/files/readme", |_: &Request| "static")
+            .get("/files/{name}/raw", |_: &Request| "param raw");
+
+        assert_eq!(call(&router, RequestMethod::GET, "/files/readme").unwrap().0, "static");
+        let (body, params) = call(&router, RequestMethod::GET, "/files/notes").unwrap();
+        assert_eq!((body.as_str(), params["name"].as_str()), ("param", "notes"));
+        assert_eq!(call(&router, RequestMethod::GET, "/files/notes/raw").unwrap().0, "param raw");
+        let (body, params) = call(&router, RequestMethod::GET, "/files/a/b/c").unwrap();
+        assert_eq!((body.as_str(), params["path"].as_str()), ("wildcard", "a/b/c"));
+        assert!(call(&router, RequestMethod::GET, "/other").is_none());
+        assert!(call(&router, RequestMethod::POST, "/files/readme").is_none());
+    }
+
+    #[test]
+    fn equal_routes_keep_the_registration_order() {
+        let router = Router::new()
+            .get("/people/{id}", |_: &Request| "first")
+            .get("/people/{name}", |_: &Request| "second");
+        let (body, params) = call(&router, RequestMethod::GET, "/people/7").unwrap();
+        assert_eq!((body.as_str(), params["id"].as_str()), ("first", "7"));
+    }
+
+    #[test]
+    fn static_mount_only_allows_the_paths_of_its_files() {
+        let root = std::env::temp_dir().join(format!("http-server-router-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("style.css"), "body {}").unwrap();
+        let router = Router::new()
+            .post("/people", |_: &Request| "created")
+            .mount_static("/", StaticFiles::new(&root));
+
+        assert_eq!(router.allowed_methods("/style.css"), [RequestMethod::GET, RequestMethod::HEAD, RequestMethod::OPTIONS]);
+        assert_eq!(router.allowed_methods("/people"), [RequestMethod::POST, RequestMethod::OPTIONS]);
+        assert!(router.allowed_methods("/nothing").is_empty());
+        assert_eq!(router.allowed_methods("*").len(), 4);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,344 +1,535 @@
-use std::fs;
 use std::thread;
-use std::io::{prelude::*};
-use std::net::{TcpStream, TcpListener};
-use crate::response_message::{ResponseMessage, StatusCode, send_response_error, create_response_header};
-use crate::database;
-use serde::Deserialize;
-use std::collections;
-
-enum RequestMethod {
-    GET,
-    POST,
-    PUT,
-    PATCH,
-    DELETE,
-    Invalid,
-}
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::net::{SocketAddr, TcpStream, TcpListener};
+use crate::response_message::StatusCode;
+use crate::config::{ServerConfig, QueueFullPolicy};
+use crate::pool::{ThreadPool, Job};
+use crate::response::{Response, IntoResponse};
+use crate::error::ServerError;
+use crate::request::{Request, RequestReader, RequestMethod, ParseError};
+use crate::router::Router;
+use crate::shutdown::{ServerHandle, Connections, ConnectionGuard};
+// time between the checks of new connections and of the shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// waits after an accept error, doubled on each error in a row, like when the
+// process has too many open files
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
 
-#[derive(Debug, Deserialize)]
-struct PeopleToDelete {
-    id: i64,
-}
-#[derive(Debug, Deserialize)]
-struct People {
-    name: String,
-    age: String,
+// server listening in an address, it runs until the shutdown of its handle
+pub struct Server {
+    listener: TcpListener,
+    state: Arc<ServerState>,
+    pool: ThreadPool,
 }
-#[derive(Debug, Deserialize)]
-struct CalcRequest {
-    operation: String,
-    number1: f64,
-    number2: f64,
-}
-struct Request {
-    method: RequestMethod,
-    path: String,
-    headers: collections::HashMap<String, String>,
+
+// state shared with the threads that serve the connections
+struct ServerState {
+    config: ServerConfig,
+    router: Router,
+    handle: ServerHandle,
+    connections: Arc<Connections>,
+    // accepted connections, queued or being served
+    open_connections: AtomicUsize,
 }
-struct Route<'a> {
-    method: &'a RequestMethod,
-    path: &'a String,
+
+// counts a connection as open until it is dropped, after it is served or rejected.
+// it is registered in the connections when it is accepted, so the shutdown waits for the queued ones
+struct OpenConnection {
+    state: Arc<ServerState>,
+    connection: Option<ConnectionGuard>,
 }
-struct Response {
-    status_code: StatusCode,
-    headers: collections::HashMap<String, String>,
+
+// main function to connect server, it runs until SIGINT or SIGTERM
+pub fn try_server_connect(config: ServerConfig, router: Router, pool: ThreadPool) -> io::Result<()> {
+    let server = Server::bind(config, router, pool)?;
+    if let Err(err) = server.handle().register_signals() {
+        log_warn!("an error ocurred to register the shutdown signals!\n{}", err);
+    }
+    log_info!("the server is listening in {}", server.local_addr()?);
+    server.run();
+    Ok(())
 }
 
-impl RequestMethod {
-    fn parse_request_method(str_method: &str) -> Self {
-        match str_method {
-            "GET" => Self::GET,
-            "POST" => Self::POST,
-            "PUT" => Self::PUT,
-            "PATCH" => Self::PATCH,
-            "DELETE" => Self::DELETE,
-            _ => Self::Invalid,
+impl Server {
+    // listen in the address and port of the config
+    pub fn bind(config: ServerConfig, router: Router, pool: ThreadPool) -> io::Result<Server> {
+        let listener = TcpListener::bind((config.address, config.port))?;
+        // the accept loop checks the shutdown between connections
+        listener.set_nonblocking(true)?;
+        let state = ServerState {
+            config, router, handle: ServerHandle::new(), connections: Arc::new(Connections::new()), open_connections: AtomicUsize::new(0),
+        };
+        Ok(Server { listener, state: Arc::new(state), pool })
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    pub fn handle(&self) -> ServerHandle {
+        self.state.handle.clone()
+    }
+
+    // accept connections until the shutdown, then wait for the requests in progress.
+    // the connections are served by the threads of the pool
+    pub fn run(self) {
+        let config = &self.state.config;
+        let mut backoff = ACCEPT_BACKOFF_MIN;
+        while !self.state.handle.is_shutdown() {
+            let at_capacity = self.state.open_connections.load(Ordering::SeqCst) >= config.max_connections;
+            // the new connections wait in the backlog of the listener
+            if at_capacity && config.queue_full_policy == QueueFullPolicy::Block {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            match self.listener.accept() {
+                Ok((s, _)) => {
+                    log_debug!("connected!");
+                    backoff = ACCEPT_BACKOFF_MIN;
+                    if let Err(err) = s.set_nonblocking(false).and_then(|_| s.set_write_timeout(Some(config.write_timeout))) {
+                        log_warn!("an error ocurred to configure the connection!\n{}", err);
+                        continue;
+                    }
+                    match config.queue_full_policy {
+                        QueueFullPolicy::Reject { retry_after } if at_capacity => reject_connection(s, retry_after),
+                        _ => self.serve(s),
+                    }
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(err) => {
+                    log_error!("an error ocurred to accept a connection, retrying in {:?}!\n{}", backoff, err);
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(ACCEPT_BACKOFF_MAX);
+                },
+            }
         }
+        self.drain();
     }
-}
-impl Response {
-    fn new_response(status_code: StatusCode, headers: collections::HashMap<String, String>) -> Self {
-        Response { status_code: status_code, headers: headers, }
+
+    fn serve(&self, s: TcpStream) {
+        let rejected = match self.state.config.queue_full_policy {
+            QueueFullPolicy::Reject { retry_after } => s.try_clone().ok().map(|s| (s, retry_after)),
+            QueueFullPolicy::Block => None,
+        };
+        let open = OpenConnection::new(Arc::clone(&self.state), &s);
+        let job: Job = Box::new(move || {
+            let open = open;
+            if let Some(connection) = &open.connection {
+                server_handle(s, &open.state, connection);
+            }
+        });
+        match rejected {
+            Some((stream, retry_after)) => {
+                if self.pool.try_execute(job).is_err() {
+                    reject_connection(stream, retry_after);
+                }
+            },
+            None => self.pool.execute(job),
+        }
     }
-    fn parse_response(&self, body_content: &str) -> Vec<u8> {
-        let mut response = String::new();
-        response.push_str(&format!("HTTP/1.1 {}\r\n", self.status_code));
-        for (k, v) in &self.headers {
-            response.push_str(&format!("{}: {}\r\n", k, v));
+
+    // close the listener and wait the connections until the shutdown timeout,
+    // the connections still open after it are closed
+    fn drain(self) {
+        log_info!("shutting down the server...");
+        let Server { listener, state, pool } = self;
+        drop(listener);
+        let deadline = Instant::now() + state.config.shutdown_timeout;
+        while !state.connections.is_empty() && Instant::now() < deadline {
+            state.connections.close_idle();
+            thread::sleep(POLL_INTERVAL);
         }
-        response.push_str("\r\n");
-        response.push_str(body_content);
-        response.into_bytes()
+        if !state.connections.is_empty() {
+            log_warn!("{} connections didn't finish before the shutdown timeout!", state.connections.len());
+            state.connections.close_all();
+        }
+        // waits the workers, the queued connections were served in the drain
+        drop(pool);
+        log_info!("the server was stopped!");
     }
 }
 
-impl<'a> Route<'a> {
-    fn new_route(method: &'a RequestMethod, path: &'a String) -> Self {
-        Route { method, path }
+impl OpenConnection {
+    fn new(state: Arc<ServerState>, stream: &TcpStream) -> Self {
+        state.open_connections.fetch_add(1, Ordering::SeqCst);
+        let connection = state.connections.register(stream);
+        OpenConnection { state, connection }
     }
 }
 
-// main function to connect server
-pub fn try_server_connect(port: &'static str) {
-    // create a tcp socket in localhost
-    let stream = TcpListener::bind(&format!("127.0.0.1:{}", port)).unwrap();
-    for stream in stream.incoming() {
-        match stream {
-            Ok(s) => {
-                println!("connected!");
-                thread::spawn(|| { server_handle(s); });
-            },
-            Err(err) => panic!("an error ocurred to try the server connect!\n{}", err),
-        }
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.state.open_connections.fetch_sub(1, Ordering::SeqCst);
     }
 }
 
-pub fn server_handle(mut stream: TcpStream) {
-    // buffer to read data sent by client
-    let mut buffer = [0u8; 4064];
-    let n = stream.read(&mut buffer).unwrap();
-    let request_str = String::from_utf8_lossy(&buffer[..n]);
-    // get parts of request to separate headers of body
-    let req_parts: Vec<&str> = request_str.split("\r\n\r\n").collect();
-    // request headers
-    let req_headers = req_parts[0];
-    // request body
-    let req_body = req_parts.get(1).unwrap_or(&"").trim();
-    // request line
-    // METHOD /PATH HTTP/1.1
-    let request_line: Option<&str> = req_headers.lines().next();
-    // display headers and body of request
-    println!("new request:\nheaders:{:#?}\nbody: {:#?}\n", req_headers, req_body);
-
-    let success_status_code = StatusCode::Success;
-    let mut request_method = String::new();
-    let mut request_path = String::new();
-
-    // get and store method and path of request line to use to process requests
-    match request_line {
-        Some(line) => {
-            let mut parts = line.split_whitespace();
-            if let Some(method) = parts.next() {
-                request_method = method.to_string();
+// answer 503 to a connection that can't be served now, without reading its request
+fn reject_connection(stream: TcpStream, retry_after: Duration) {
+    // the accept loop can't wait for a slow client
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+    let mut response = ServerError::Unavailable("the server is busy, try again later".to_string()).into_response();
+    let _ = response.headers.insert("Retry-After", &retry_after.as_secs().max(1).to_string());
+    response.set_keep_alive(None);
+    let _ = response.write_to(&stream, true, true);
+}
+
+// serve the requests of a connection until the client or the server closes it,
+// pipelined requests are read from the buffer of the reader one by one
+fn server_handle(stream: TcpStream, state: &ServerState, connection: &ConnectionGuard) {
+    let ServerState { config, router, handle, .. } = state;
+    let mut reader = RequestReader::new(&stream);
+    let mut served_requests = 0;
+    loop {
+        // the idle connections are closed on shutdown, the flag is checked after
+        // marking it as idle so the shutdown can't be missed
+        let shutdown = handle.is_shutdown() || {
+            connection.set_idle(true);
+            handle.is_shutdown()
+        };
+        if shutdown {
+            // a request that was already sent, like the one of a connection that waited
+            // in the queue, is still served and answered with Connection: close
+            connection.set_idle(false);
+            if !reader.has_request(POLL_INTERVAL) {
+                return;
             }
-            if let Some(path) = parts.next() {
-                request_path = path.to_string();
+        }
+        // read and parse the request sent by client, the connection is closed
+        // when the client stays idle for too long. it stops being idle with the first
+        // byte of the request, so a request that is still arriving isn't closed on shutdown
+        let read = reader.read_request_with(&config.request_limits, &config.request_timeouts, || connection.set_idle(false));
+        let mut request = match read {
+            Ok(request) => request,
+            Err(ParseError::Closed) => return,
+            Err(ParseError::Io(err)) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return,
+            Err(err) => {
+                log_warn!("an error ocurred to read the request!\n{}", err);
+                if err.status_code().is_some() {
+                    let mut response = ServerError::Parse(err).into_response();
+                    response.set_keep_alive(None);
+                    let _ = response.write_to(&stream, true, true);
+                }
+                return;
             }
-        },
-        _ => eprintln!("an error ocurred to read the request line!"),
+        };
+        served_requests += 1;
+        // display headers and body of request
+        log_debug!("new request:\nheaders:{:#?}\nbody: {:#?}\n", request.headers, request.body_str());
+
+        let mut response = dispatch_isolated(router, &mut request);
+        let http11 = request.version == "HTTP/1.1";
+        // responses to HEAD are sent without body, with the same headers of GET
+        let include_body = request.method != RequestMethod::HEAD;
+        // a streamed body sent to a HTTP/1.0 client ends when the connection is closed
+        let keep_alive = !handle.is_shutdown()
+            && request.wants_keep_alive()
+            && served_requests < config.max_requests_per_connection
+            && (http11 || !include_body || !response.is_stream());
+        let keep_alive_params = format!("timeout={}, max={}", config.request_timeouts.idle.as_secs(), config.max_requests_per_connection - served_requests);
+        response.set_keep_alive(keep_alive.then_some(keep_alive_params));
+        if let Err(err) = response.write_to(&stream, http11, include_body) {
+            log_warn!("an error ocurred to send the response!\n{}", err);
+            return;
+        }
+        if !keep_alive {
+            return;
+        }
     }
+}
 
-    let html_content_type = "text/html; charset=utf-8";
-    let js_content_type = "application/javascript";
-    let text_content_type = "text/plain";
-    let json_content_type = "application/json";
-    let css_content_type = "text/css";
-    // to store filenames to send to client/browser
-    let mut filename = String::new();
-
-    // process and respond requests
-    match RequestMethod::parse_request_method(&request_method) {
-        RequestMethod::GET => {
-            match request_path.as_str() {
-                "/" => filename.push_str("home.html"),
-                "/calculator" => filename.push_str("calc.html"),
-                "/create-people" => filename.push_str("people.html"),
-                "/find-people" => {
-                    if let Ok(conn) = database::connect_db() {
-                        match database::fetch_people(&conn) {
-                            Ok(data) => {
-                                let hash_string = serde_json::to_string(&data).unwrap();
-                                let response = Response::new_response(success_status_code, create_response_header(json_content_type, &hash_string));
-                                stream.write_all(&response.parse_response(&hash_string)).unwrap();
-                            },
-                            Err(err) => {
-                                eprintln!("an error ocurred to fetch people!");
-                                let body_content = format!( r#"{{ "status": "err", "message": "{}" }}"#, err);
-                                send_response_error(&stream, ResponseMessage::badrequest_error(&body_content, json_content_type).content);
-                            }
-                        }
-                    }
-                },
-                "/people.css" => {
-                    // get the css content of file
-                    let css = fs::read_to_string("src/public/people.css").unwrap();
-                    let response = Response::new_response(
-                        success_status_code, create_response_header(css_content_type, &css)
-                    );
-                    stream.write_all(&response.parse_response(&css));
-                },
-                "/calc.css" => {
-                    // get the css content of file
-                    let css = fs::read_to_string("src/public/calc.css").unwrap();
-                    let response = Response::new_response(
-                        success_status_code, create_response_header(css_content_type, &css)
-                    );
-                    stream.write_all(&response.parse_response(&css));
-                }
-                "/people.js" => {
-                    // get the javascript content of file
-                    let js = fs::read_to_string("src/public/people.js").unwrap();
-                    let body_content = &format!("console.log('people.js was loaded!!');\n{}", &js);
-                    let response = Response::new_response(
-                        success_status_code, create_response_header(js_content_type, body_content)
-                    );
-                    stream.write_all(&response.parse_response(body_content));
-                }
-                "/calc.js" => {
-                    // get the javascript content of file
-                    let js = fs::read_to_string("src/public/calc.js").unwrap();
-                    let body_content = &format!("console.log('hello world!');\n{}", &js);
-                    let response = Response::new_response(
-                        success_status_code, create_response_header(js_content_type, body_content)
-                    );
-                    stream.write_all(&response.parse_response(body_content));
-                }
-                _ => {
-                    send_response_error(&stream, ResponseMessage::not_found_error("page not found!", text_content_type).content);
-                },
-            }
-        },
-        RequestMethod::POST => {
-            // get the json body sent in the post request
-            let json_body = req_body.trim_matches(|c: char| c.is_whitespace());
-            // parse original json body to string
-            let json_deserialize = serde_json::to_string_pretty(json_body);
-            match request_path.as_str() {
-                "/create-people" => {
-                    if let Ok(result) = json_deserialize {
-                        let inner_json: Option<String> = serde_json::from_str(&result.to_string()).unwrap();
-                        match inner_json {
-                            Some(j) => {
-                                let people_parsed: People = serde_json::from_str(&j).unwrap();
-                                if let Ok(conn) = database::connect_db() {
-                                    match database::insert_new_people(&conn, &people_parsed.name, &people_parsed.age) {
-                                        Ok(_) => {
-                                            let body_content = &format!("People was created!");
-                                            let response = Response::new_response(
-                                                success_status_code, create_response_header(text_content_type, body_content)
-                                            );
-                                            stream.write_all(&response.parse_response(body_content));
-                                        },
-                                        Err(err) => {
-                                            eprintln!("an error ocurred to create the people!");
-                                            let body_content = format!( r#"{{ "status": "err", "message": "{}" }}"#, err);
-                                            send_response_error(&stream, ResponseMessage::badrequest_error(&body_content, json_content_type).content);
-                                        }
-                                    }
-                                }
-                            },
-                            _ => eprintln!("an error ocurred to parse json!")
-                        }
-                    }
-                },
-                "/calculator" => {
-                    if let Ok(result) = json_deserialize {
-                        let inner_json: Option<String> = serde_json::from_str(&result.to_string()).unwrap();
-                        match inner_json {
-                            Some(j) => {
-                                // parse json to struct
-                                let calc_parsed: CalcRequest = serde_json::from_str(&j).unwrap();
-                                let operation = calc_parsed.operation;
-                                let number1 = calc_parsed.number1;
-                                let number2 = calc_parsed.number2;
-
-                                match operation.as_str() {
-                                    "sum" => {
-                                        let sum = number1 + number2;
-                                        let body_content = &format!("the sum is: {}", sum);
-                                        let response = Response::new_response(
-                                            success_status_code, create_response_header(text_content_type, body_content)
-                                        );
-                                        stream.write_all(&response.parse_response(body_content));
-                                    },
-                                    "sub" => {
-                                        let sub = number1 - number2;
-                                        let body_content = &format!("the sub is: {}", sub);
-                                        let response = Response::new_response(
-                                            success_status_code, create_response_header(text_content_type, body_content)
-                                        );
-                                        stream.write_all(&response.parse_response(body_content));
-                                    },
-                                    "mult" => {
-                                        let mult = number1 * number2;
-                                        let body_content = &format!("the mult is: {}", mult);
-                                        let response = Response::new_response(
-                                            success_status_code, create_response_header(text_content_type, body_content)
-                                        );
-                                        stream.write_all(&response.parse_response(body_content));
-                                    },
-                                    "div" => {
-                                        let div = number1 / number2;
-                                        let body_content = &format!("the div is: {}", div);
-                                        let response = Response::new_response(
-                                            success_status_code, create_response_header(text_content_type, body_content)
-                                        );
-                                        stream.write_all(&response.parse_response(body_content));
-                                    }
-                                    _ => eprintln!("an error ocurred to parse json!")
-                                }
-                            },
-                            None => eprintln!("any json received"),
-                        }
-                    }
-                },
-                _ => {
-                    send_response_error(&stream, ResponseMessage::not_found_error("route not found!", text_content_type).content);
-                },
-            }
-        },
-        RequestMethod::DELETE => {
-            match request_path.as_str() {
-                "/delete-people" => {
-                    let json_body = req_body.trim_matches(|c: char| c.is_whitespace());
-                    let json_deserialize = serde_json::to_string_pretty(json_body);
-                    if let Ok(result) = json_deserialize {
-                        let inner_json: Option<String> = serde_json::from_str(&result.to_string()).unwrap();
-                        match inner_json {
-                            Some(j) => {
-                                let people_id: PeopleToDelete = serde_json::from_str(&j).unwrap();
-                                if let Ok(conn) = database::connect_db() {
-                                    match database::delete_people(&conn, people_id.id) {
-                                        Ok(_) => {
-                                            let response = Response::new_response(success_status_code, create_response_header(text_content_type, "people was deleted!"));
-                                            stream.write_all(&response.parse_response("people was deleted!")).unwrap();
-                                        },
-                                        Err(err) => {
-                                            eprintln!("an error ocurred to try to delete people!!");
-                                            let body_content = format!( r#"{{ "status": "err", "message": "{}" }}"#, err);
-                                            send_response_error(&stream, ResponseMessage::badrequest_error(&body_content, json_content_type).content);
-                                        }
-                                    }
-                                }
-                            },
-                            _ => {
-                                eprintln!("an error ocurred to parse json!!");
-                            }
-                        }
-                    }
-                },
-                _ => {
-                    send_response_error(&stream, ResponseMessage::not_found_error("route not found!", text_content_type).content);
-                },
-            }
+// dispatch the request behind a panic boundary, a handler that panics
+// is answered with 500 and the connection keeps working
+fn dispatch_isolated(router: &Router, request: &mut Request) -> Response {
+    match panic::catch_unwind(AssertUnwindSafe(|| dispatch(router, request))) {
+        Ok(response) => response,
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().copied()
+                .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+                .unwrap_or("unknown panic");
+            log_error!("the handler of {} {} panicked!\n{}", request.method, request.target, message);
+            ServerError::Internal(message.to_string()).into_response()
         }
-        _ => eprintln!("..."),
     }
+}
 
-    let html_file_content = read_html_file(&filename);
-    match html_file_content {
-        Ok(response_content) => {
-            let response = Response::new_response(success_status_code, create_response_header(html_content_type, &response_content));
-            stream.write_all(&response.parse_response(&response_content));
-        },
-        Err(err) => eprintln!("\nan error ocurred to get response content\n{}", err),
+// call the handler of the route matched by the request. when the path exists with
+// other methods, OPTIONS is answered with the allowed methods and any other method with 405
+fn dispatch(router: &Router, request: &mut Request) -> Response {
+    if request.method == RequestMethod::Invalid {
+        return ServerError::NotImplemented("method not implemented!".to_string()).into_response();
+    }
+    if let Some(route) = router.find(request.method, request.path()) {
+        request.params = route.params;
+        return (route.handler)(request);
+    }
+
+    let allowed_methods = router.allowed_methods(request.path());
+    if allowed_methods.is_empty() {
+        return ServerError::NotFound("page not found!".to_string()).into_response();
+    }
+    let allow = allowed_methods.iter().map(|m| m.as_str()).collect::<Vec<&str>>().join(", ");
+    if request.method == RequestMethod::OPTIONS {
+        Response::builder().status(StatusCode::NoContent).header("Allow", &allow).empty()
+    } else {
+        ServerError::MethodNotAllowed { allow }.into_response()
     }
 }
 
-fn read_html_file(file_name: &str) -> std::io::Result<String> {
-    let mut html_file = fs::File::open("src/public/".to_string() + file_name)?;
-    let mut html_content = String::new();
-    html_file.read_to_string(&mut html_content)?;
-    Ok(html_content)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use crate::request::RequestTimeouts;
+
+    fn request(method: RequestMethod, target: &str) -> Request {
+        Request {
+            method, target: target.to_string(), version: "HTTP/1.1".to_string(), headers: Default::default(),
+            body: Vec::new(), trailers: Default::default(), params: Default::default(),
+        }
+    }
+
+    fn people_router() -> Router {
+        Router::new()
+            .get("/people/{id}", |_: &Request| "people")
+            .delete("/people/{id}", |_: &Request| "deleted")
+    }
+
+    #[test]
+    fn other_methods_of_a_path_are_answered_with_405_and_the_allowed_methods() {
+        let response = dispatch(&people_router(), &mut request(RequestMethod::POST, "/people/1?full=true"));
+        assert_eq!(response.status_code, StatusCode::MethodNotAllowed);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD, DELETE, OPTIONS"));
+        assert_eq!(response.headers.get("Content-Type"), Some("application/problem+json"));
+
+        let response = dispatch(&people_router(), &mut request(RequestMethod::PUT, "/other"));
+        assert_eq!(response.status_code, StatusCode::NotFound);
+        assert!(response.headers.get("Allow").is_none());
+    }
+
+    #[test]
+    fn options_is_answered_with_the_allowed_methods() {
+        let response = dispatch(&people_router(), &mut request(RequestMethod::OPTIONS, "/people/1"));
+        assert_eq!(response.status_code, StatusCode::NoContent);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD, DELETE, OPTIONS"));
+    }
+
+    #[test]
+    fn unknown_methods_are_answered_with_501() {
+        let response = dispatch(&people_router(), &mut request(RequestMethod::Invalid, "/people/1"));
+        assert_eq!(response.status_code, StatusCode::NotImplemented);
+        assert_eq!(response.headers.get("Content-Type"), Some("application/problem+json"));
+        assert!(response.headers.get("Allow").is_none());
+    }
+
+    #[test]
+    fn head_is_answered_with_the_headers_of_get_and_no_body() {
+        let config = ServerConfig { port: 0, ..ServerConfig::default() };
+        let server = Server::bind(config, people_router(), ThreadPool::new(2, 2)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"HEAD /people/1 HTTP/1.1\r\nHost: test\r\n\r\nGET /people/1 HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        handle.shutdown();
+        running.join().unwrap();
+
+        // the GET response follows the headers of HEAD, so HEAD didn't send a body
+        let (head, get) = response.split_at(response.rfind("HTTP/1.1 200 OK").unwrap());
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Length: 6\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+        assert!(get.contains("Content-Length: 6\r\n"));
+        assert!(get.ends_with("\r\n\r\npeople"));
+    }
+
+    #[test]
+    fn a_panicking_handler_is_answered_with_500_and_the_connection_keeps_working() {
+        let router = people_router().get("/panic", |_: &Request| -> &str { panic!("the handler failed") });
+        let config = ServerConfig { port: 0, ..ServerConfig::default() };
+        let server = Server::bind(config, router, ThreadPool::new(1, 2)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"GET /panic HTTP/1.1\r\nHost: test\r\n\r\nGET /people/1 HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        handle.shutdown();
+        running.join().unwrap();
+
+        let (panicked, next) = response.split_at(response.find("HTTP/1.1 200 OK").unwrap());
+        assert!(panicked.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(panicked.contains("Content-Type: application/problem+json\r\n"));
+        assert!(!panicked.contains("Connection: close"));
+        assert!(next.ends_with("\r\n\r\npeople"));
+    }
+
+    #[test]
+    fn connections_over_the_queue_are_answered_with_503() {
+        let policy = QueueFullPolicy::Reject { retry_after: Duration::from_secs(2) };
+        let config = ServerConfig { port: 0, queue_full_policy: policy, ..ServerConfig::default() };
+        let server = Server::bind(config, people_router(), ThreadPool::new(1, 1)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        // the first connection keeps the worker busy and the second fills the queue
+        let mut busy = TcpStream::connect(addr).unwrap();
+        busy.write_all(b"GET /people/1 HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+        let mut response = [0; 512];
+        assert!(busy.read(&mut response).unwrap() > 0);
+        let _queued = TcpStream::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        let mut rejected = TcpStream::connect(addr).unwrap();
+        let mut response = String::new();
+        rejected.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("Retry-After: 2\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+        assert!(response.contains("Content-Type: application/problem+json\r\n"));
+
+        handle.shutdown();
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn a_stalled_header_is_answered_with_408() {
+        let timeouts = RequestTimeouts { header: Duration::from_millis(300), ..RequestTimeouts::default() };
+        let config = ServerConfig { port: 0, request_timeouts: timeouts, ..ServerConfig::default() };
+        let server = Server::bind(config, people_router(), ThreadPool::new(1, 1)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        // the client keeps sending bytes, so only the deadline of the header can stop it
+        let mut client = TcpStream::connect(addr).unwrap();
+        let started = Instant::now();
+        client.write_all(b"GET /people/1 HTTP/1.1\r\nHost: test\r\n").unwrap();
+        client.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let mut response = [0; 512];
+        let mut len = 0;
+        while len == 0 && started.elapsed() < Duration::from_secs(5) {
+            client.write_all(b"X-Slow: a\r\n").unwrap();
+            len = client.read(&mut response).unwrap_or(0);
+        }
+        let response = String::from_utf8_lossy(&response[..len]);
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "{}", response);
+        assert!(response.contains("Connection: close\r\n"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        handle.shutdown();
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn streams_to_http10_clients_end_by_closing_the_connection() {
+        let router = Router::new().get("/stream", |_: &Request| Response::builder().stream(&b"hello world"[..]));
+        let config = ServerConfig { port: 0, ..ServerConfig::default() };
+        let server = Server::bind(config, router, ThreadPool::new(1, 1)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        // the keep-alive asked by the client can't be kept, the closing ends the body
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        handle.shutdown();
+        running.join().unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("Connection: close\r\n") || head.ends_with("Connection: close"));
+        assert!(!head.contains("Transfer-Encoding") && !head.contains("Content-Length"));
+        assert_eq!(body, "hello world");
+    }
+
+    #[test]
+    fn shutdown_waits_requests_and_closes_idle_connections() {
+        let router = Router::new().get("/", |_: &Request| "hello");
+        let config = ServerConfig { port: 0, ..ServerConfig::default() };
+        let server = Server::bind(config, router, ThreadPool::new(2, 2)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+        let mut response = [0; 512];
+        let len = client.read(&mut response).unwrap();
+        assert!(response[..len].starts_with(b"HTTP/1.1 200 OK"));
+
+        // the connection is idle and kept alive, the shutdown closes it before the idle timeout
+        let started = Instant::now();
+        handle.shutdown();
+        running.join().unwrap();
+        assert!(started.elapsed() < ServerConfig::default().request_timeouts.idle);
+        assert_eq!(client.read(&mut response).unwrap(), 0);
+        assert!(TcpStream::connect(addr).is_err());
+    }
+
+    #[test]
+    fn shutdown_waits_requests_that_are_still_arriving() {
+        let router = Router::new().post("/", |request: &Request| request.body_str());
+        let config = ServerConfig { port: 0, ..ServerConfig::default() };
+        let server = Server::bind(config, router, ThreadPool::new(2, 2)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        handle.shutdown();
+        // the drain closes the idle connections in its first poll
+        thread::sleep(POLL_INTERVAL * 4);
+        client.write_all(b"\r\nhello").unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("hello"));
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown_serves_the_queued_connections() {
+        let router = Router::new()
+            .post("/", |request: &Request| request.body_str())
+            .get("/queued", |_: &Request| "queued");
+        let config = ServerConfig { port: 0, ..ServerConfig::default() };
+        let server = Server::bind(config, router, ThreadPool::new(1, 4)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.handle();
+        let running = thread::spawn(move || server.run());
+
+        // the only worker waits for the body of the first request, the second connection is queued
+        let mut busy = TcpStream::connect(addr).unwrap();
+        busy.write_all(b"POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n\r\n").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        let mut queued = TcpStream::connect(addr).unwrap();
+        queued.write_all(b"GET /queued HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+        thread::sleep(Duration::from_millis(200));
+        handle.shutdown();
+        thread::sleep(POLL_INTERVAL * 4);
+        busy.write_all(b"hello").unwrap();
+
+        for (client, body) in [(&mut busy, "hello"), (&mut queued, "queued")] {
+            let mut response = Vec::new();
+            client.read_to_end(&mut response).unwrap();
+            let response = String::from_utf8_lossy(&response);
+            assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+            assert!(response.contains("Connection: close"), "{}", response);
+            assert!(response.ends_with(body));
+        }
+        running.join().unwrap();
+    }
 }
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// handle to stop a running server, it can be cloned and sent to other threads.
+// the server stops accepting connections, waits for the requests in progress and closes
+// the idle keep-alive connections
+#[derive(Clone, Default)]
+pub struct ServerHandle {
+    shutdown: Arc<AtomicBool>,
+}
+
+// connections accepted by the server, queued or being served. the shutdown
+// waits for them and closes the idle ones
+#[derive(Default)]
+pub struct Connections {
+    next_id: AtomicU64,
+    streams: Mutex<HashMap<u64, Connection>>,
+}
+
+struct Connection {
+    stream: TcpStream,
+    // waiting for the next request, it can be closed without losing a response
+    idle: bool,
+}
+
+// removes the connection from the list when it ends, even if the handler panics
+pub struct ConnectionGuard {
+    connections: Arc<Connections>,
+    id: u64,
+}
+
+impl ServerHandle {
+    pub fn new() -> Self {
+        ServerHandle::default()
+    }
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+    // SIGINT and SIGTERM start the shutdown of the server
+    pub fn register_signals(&self) -> io::Result<()> {
+        for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+            signal_hook::flag::register(signal, Arc::clone(&self.shutdown))?;
+        }
+        Ok(())
+    }
+}
+
+impl Connections {
+    pub fn new() -> Self {
+        Connections::default()
+    }
+    // add a connection to the list, `None` if its stream couldn't be cloned.
+    // it isn't idle until its handler starts waiting for a request
+    pub fn register(self: &Arc<Self>, stream: &TcpStream) -> Option<ConnectionGuard> {
+        let stream = stream.try_clone().ok()?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, Connection { stream, idle: false });
+        Some(ConnectionGuard { connections: Arc::clone(self), id })
+    }
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+    // stop reading the idle connections, their handlers see the connection closed by the client
+    pub fn close_idle(&self) {
+        for connection in self.lock().values().filter(|c| c.idle) {
+            let _ = connection.stream.shutdown(Shutdown::Read);
+        }
+    }
+    // close all the connections, used when the requests don't finish before the deadline
+    pub fn close_all(&self) {
+        for connection in self.lock().values() {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Connection>> {
+        // the map stays valid even if a thread panicked while holding the lock
+        self.streams.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl ConnectionGuard {
+    pub fn set_idle(&self, idle: bool) {
+        if let Some(connection) = self.connections.lock().get_mut(&self.id) {
+            connection.idle = idle;
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.lock().remove(&self.id);
+    }
+}
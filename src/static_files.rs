@@ -0,0 +1,244 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::response_message::StatusCode;
+use crate::error::{problem, ServerError};
+use crate::response::{Response, IntoResponse};
+use crate::request::percent_decode;
+
+const INDEX_FILE: &str = "index.html";
+
+// what to do when the requested path goes through a symbolic link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    Follow,
+    // follow only the links whose target is inside the document root
+    FollowWithinRoot,
+    Deny,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy.to_ascii_lowercase().as_str() {
+            "follow" => Ok(SymlinkPolicy::Follow),
+            "within_root" => Ok(SymlinkPolicy::FollowWithinRoot),
+            "deny" => Ok(SymlinkPolicy::Deny),
+            _ => Err("expected follow, within_root or deny".to_string()),
+        }
+    }
+}
+
+// serves the files of a directory, like the src/public folder
+pub struct StaticFiles {
+    root: PathBuf,
+    symlink_policy: SymlinkPolicy,
+}
+
+// content type sent for each file extension
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        StaticFiles { root: root.into(), symlink_policy: SymlinkPolicy::FollowWithinRoot }
+    }
+    pub fn with_symlink_policy(mut self, symlink_policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = symlink_policy;
+        self
+    }
+
+    // serve the file in the relative path of the root, directories serve their index.html
+    pub fn serve(&self, relative_path: &str) -> Response {
+        match self.resolve(relative_path) {
+            Ok(path) => self.serve_file(&path),
+            Err(StatusCode::BadRequest) => ServerError::BadRequest("invalid path!".to_string()).into_response(),
+            Err(StatusCode::Forbidden) => problem(StatusCode::Forbidden, "forbidden path!"),
+            Err(_) => not_found(),
+        }
+    }
+    // there is a file to serve in the relative path
+    pub fn contains(&self, relative_path: &str) -> bool {
+        self.resolve(relative_path).is_ok_and(|path| path.is_file())
+    }
+
+    // decode and normalize the raw path of the request to a path inside the root.
+    // malformed paths are rejected with 400 and the paths that escape the root with 403
+    fn resolve(&self, relative_path: &str) -> Result<PathBuf, StatusCode> {
+        let decoded = percent_decode(relative_path).ok_or(StatusCode::BadRequest)?;
+        let decoded = String::from_utf8(decoded).map_err(|_| StatusCode::BadRequest)?;
+        if decoded.contains('\0') || decoded.contains('\\') {
+            return Err(StatusCode::BadRequest);
+        }
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in decoded.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => if segments.pop().is_none() { return Err(StatusCode::Forbidden) },
+                _ => segments.push(segment),
+            }
+        }
+
+        let mut path = self.root.clone();
+        path.extend(&segments);
+        self.check_symlinks(&path, &segments)?;
+        if path.is_dir() {
+            path.push(INDEX_FILE);
+            self.check_symlinks(&path, &[INDEX_FILE])?;
+        }
+        Ok(path)
+    }
+
+    fn check_symlinks(&self, path: &Path, segments: &[&str]) -> Result<(), StatusCode> {
+        match self.symlink_policy {
+            SymlinkPolicy::Follow => Ok(()),
+            SymlinkPolicy::Deny => {
+                // check each component from the end of the path to the root
+                let mut current = path.to_path_buf();
+                for _ in segments {
+                    if fs::symlink_metadata(&current).map(|m| m.file_type().is_symlink()).unwrap_or(false) {
+                        return Err(StatusCode::Forbidden);
+                    }
+                    current.pop();
+                }
+                Ok(())
+            },
+            SymlinkPolicy::FollowWithinRoot => {
+                // a path that doesn't exist is answered later with 404
+                let Ok(real_path) = fs::canonicalize(path) else { return Ok(()) };
+                let real_root = fs::canonicalize(&self.root).map_err(|_| StatusCode::NotFound)?;
+                if real_path.starts_with(real_root) { Ok(()) } else { Err(StatusCode::Forbidden) }
+            },
+        }
+    }
+
+    fn serve_file(&self, path: &Path) -> Response {
+        match Response::builder().file(path) {
+            Ok(response) => response,
+            Err(err) if matches!(err.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => not_found(),
+            Err(err) => read_error(path, err),
+        }
+    }
+}
+
+fn read_error(path: &Path, err: io::Error) -> Response {
+    log_error!("an error ocurred to read the file {}!\n{}", path.display(), err);
+    problem(StatusCode::InternalServerError, "an error ocurred to read the file!")
+}
+
+fn not_found() -> Response {
+    ServerError::NotFound("file not found!".to_string()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // creates a document root with a file, a folder and links inside and outside of it
+    fn setup(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!("http-server-static-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join("public");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(root.join("docs").join("index.html"), "<h1>docs</h1>").unwrap();
+        fs::write(root.join("style.css"), "body {}").unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secret.txt"), root.join("outside-link.txt")).unwrap();
+            std::os::unix::fs::symlink(root.join("style.css"), root.join("inside-link.css")).unwrap();
+        }
+        (base, root)
+    }
+
+    fn status(files: &StaticFiles, path: &str) -> StatusCode {
+        files.serve(path).status_code
+    }
+
+    #[test]
+    fn serves_files_and_directory_index() {
+        let (base, root) = setup("serve");
+        let files = StaticFiles::new(&root);
+        assert_eq!(status(&files, "style.css"), StatusCode::Success);
+        assert_eq!(status(&files, ""), StatusCode::Success);
+        assert_eq!(status(&files, "docs"), StatusCode::Success);
+        assert_eq!(status(&files, "docs/../style.css"), StatusCode::Success);
+        assert_eq!(status(&files, "missing.css"), StatusCode::NotFound);
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn rejects_traversal_attacks() {
+        let (base, root) = setup("traversal");
+        let files = StaticFiles::new(&root);
+        let attacks = [
+            ("../secret.txt", StatusCode::Forbidden),
+            ("../../etc/passwd", StatusCode::Forbidden),
+            ("docs/../../secret.txt", StatusCode::Forbidden),
+            ("%2e%2e/secret.txt", StatusCode::Forbidden),
+            ("%2E%2E%2Fsecret.txt", StatusCode::Forbidden),
+            ("docs/%2e%2e/%2e%2e/secret.txt", StatusCode::Forbidden),
+            ("..%5csecret.txt", StatusCode::BadRequest),
+            ("docs\\..\\..\\secret.txt", StatusCode::BadRequest),
+            ("style.css%00.html", StatusCode::BadRequest),
+            ("%zz", StatusCode::BadRequest),
+            ("%c0%ae%c0%ae/secret.txt", StatusCode::BadRequest),
+            ("....//secret.txt", StatusCode::NotFound),
+        ];
+        for (attack, expected) in attacks {
+            assert_eq!(status(&files, attack), expected, "attack: {}", attack);
+        }
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn applies_symlink_policy() {
+        let (base, root) = setup("symlink");
+        let within_root = StaticFiles::new(&root);
+        assert_eq!(status(&within_root, "inside-link.css"), StatusCode::Success);
+        assert_eq!(status(&within_root, "outside-link.txt"), StatusCode::Forbidden);
+
+        let deny = StaticFiles::new(&root).with_symlink_policy(SymlinkPolicy::Deny);
+        assert_eq!(status(&deny, "inside-link.css"), StatusCode::Forbidden);
+        assert_eq!(status(&deny, "style.css"), StatusCode::Success);
+
+        let follow = StaticFiles::new(&root).with_symlink_policy(SymlinkPolicy::Follow);
+        assert_eq!(status(&follow, "outside-link.txt"), StatusCode::Success);
+        fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{self, DeserializeOwned, Visitor};
+use crate::handler::{FromRequest, Json, Query, Rejection};
+use crate::request::Request;
+
+// request bodies and query strings that check their fields, like
+//     errors.field("name", self.name.as_ref()).required().length(1, 100);
+pub trait Validate {
+    fn validate(&self, errors: &mut ValidationErrors);
+}
+
+// extractor that validates the value of another extractor before calling the handler,
+// like `Valid(Json(people)): Valid<Json<NewPeople>>`. invalid values are answered with 422
+pub struct Valid<T>(pub T);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+// rules applied to a field, a rule is skipped when the value is missing or a previous rule failed,
+// so each field has at most one error
+pub struct Field<'a, T: ?Sized> {
+    errors: &'a mut ValidationErrors,
+    name: &'static str,
+    value: Option<&'a T>,
+    failed: bool,
+}
+
+// integer sent as a json number or as a numeric string, like 17 or "17".
+// other values are kept to be reported by the validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Integer(Result<i64, String>);
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        ValidationErrors::default()
+    }
+    // errors with a single field, for the checks done in the handlers
+    pub fn single(field: &str, message: impl Into<String>) -> Self {
+        let mut errors = ValidationErrors::new();
+        errors.add(field, message);
+        errors
+    }
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError { field: field.to_string(), message: message.into() });
+    }
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &FieldError> {
+        self.errors.iter()
+    }
+
+    pub fn field<'a, T: ?Sized>(&'a mut self, name: &'static str, value: Option<&'a T>) -> Field<'a, T> {
+        Field { errors: self, name, value, failed: false }
+    }
+    // integer field, a value that isn't a whole number is an error
+    pub fn integer<'a>(&'a mut self, name: &'static str, value: Option<&'a Integer>) -> Field<'a, i64> {
+        let mut field = Field { errors: self, name, value: None, failed: false };
+        match value.map(|integer| &integer.0) {
+            Some(Ok(value)) => field.value = Some(value),
+            Some(Err(_)) => field.fail("must be a whole number".to_string()),
+            None => {},
+        }
+        field
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.errors.iter().map(|e| format!("{} {}", e.field, e.message)).collect();
+        write!(f, "{}", errors.join(", "))
+    }
+}
+
+impl<T: ?Sized> Field<'_, T> {
+    fn fail(&mut self, message: String) {
+        if !self.failed {
+            self.failed = true;
+            self.errors.add(self.name, message);
+        }
+    }
+    // check the value with `rule` when it is present
+    fn check(mut self, rule: impl FnOnce(&T) -> Option<String>) -> Self {
+        if !self.failed && let Some(message) = self.value.and_then(rule) {
+            self.fail(message);
+        }
+        self
+    }
+
+    pub fn required(mut self) -> Self {
+        if self.value.is_none() {
+            self.fail("is required".to_string());
+        }
+        self
+    }
+}
+
+impl Field<'_, String> {
+    // number of characters, the spaces around the text are not counted
+    pub fn length(self, min: usize, max: usize) -> Self {
+        self.check(|value| {
+            let len = value.trim().chars().count();
+            if len < min {
+                Some(if min == 1 { "can't be empty".to_string() } else { format!("must have at least {} characters", min) })
+            } else if len > max {
+                Some(format!("must have at most {} characters", max))
+            } else {
+                None
+            }
+        })
+    }
+    // every character must match `allowed`, `description` tells the client what is allowed
+    pub fn pattern(self, description: &str, allowed: impl Fn(char) -> bool) -> Self {
+        self.check(|value| (!value.chars().all(allowed)).then(|| format!("must have only {}", description)))
+    }
+}
+
+impl<T: PartialOrd + fmt::Display> Field<'_, T> {
+    pub fn range(self, min: T, max: T) -> Self {
+        self.check(|value| (*value < min || *value > max).then(|| format!("must be between {} and {}", min, max)))
+    }
+}
+
+impl Integer {
+    pub fn value(&self) -> Option<i64> {
+        self.0.as_ref().ok().copied()
+    }
+}
+
+impl<'de> Deserialize<'de> for Integer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IntegerVisitor;
+        impl Visitor<'_> for IntegerVisitor {
+            type Value = Integer;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a whole number")
+            }
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Integer, E> {
+                Ok(Integer(Ok(value)))
+            }
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Integer, E> {
+                Ok(Integer(i64::try_from(value).map_err(|_| value.to_string())))
+            }
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Integer, E> {
+                Ok(Integer(Err(value.to_string())))
+            }
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Integer, E> {
+                let trimmed = value.trim();
+                // only digits with an optional sign, "1e3" or "0x10" are not accepted
+                let digits = trimmed.strip_prefix(['-', '+']).unwrap_or(trimmed);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Ok(Integer(Err(value.to_string())));
+                }
+                Ok(Integer(trimmed.parse().map_err(|_| value.to_string())))
+            }
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<Integer, E> {
+                Ok(Integer(Err(value.to_string())))
+            }
+        }
+        deserializer.deserialize_any(IntegerVisitor)
+    }
+}
+
+impl<T: DeserializeOwned + Validate> FromRequest for Valid<Json<T>> {
+    fn from_request(request: &Request) -> Result<Self, Rejection> {
+        let Json(value) = Json::<T>::from_request(request)?;
+        validate(&value)?;
+        Ok(Valid(Json(value)))
+    }
+}
+impl<T: DeserializeOwned + Validate> FromRequest for Valid<Query<T>> {
+    fn from_request(request: &Request) -> Result<Self, Rejection> {
+        let Query(value) = Query::<T>::from_request(request)?;
+        validate(&value)?;
+        Ok(Valid(Query(value)))
+    }
+}
+
+fn validate<T: Validate>(value: &T) -> Result<(), Rejection> {
+    let mut errors = ValidationErrors::new();
+    value.validate(&mut errors);
+    if errors.is_empty() { Ok(()) } else { Err(Rejection::invalid(errors)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Body {
+        name: Option<String>,
+        age: Option<Integer>,
+    }
+    impl Validate for Body {
+        fn validate(&self, errors: &mut ValidationErrors) {
+            errors.field("name", self.name.as_ref()).required().length(1, 5).pattern("letters", char::is_alphabetic);
+            errors.integer("age", self.age.as_ref()).required().range(0, 150);
+        }
+    }
+
+    fn errors(body: &str) -> Vec<(String, String)> {
+        let body: Body = serde_json::from_str(body).unwrap();
+        let mut errors = ValidationErrors::new();
+        body.validate(&mut errors);
+        errors.iter().map(|e| (e.field.clone(), e.message.clone())).collect()
+    }
+
+    #[test]
+    fn integers_are_accepted_as_numbers_and_numeric_strings() {
+        assert!(errors(r#"{"name": "ana", "age": 17}"#).is_empty());
+        assert!(errors(r#"{"name": "ana", "age": " 17 "}"#).is_empty());
+        for age in [r#""abc""#, r#""1e3""#, "17.5", "true", r#""""#] {
+            let body = format!(r#"{{"name": "ana", "age": {}}}"#, age);
+            assert_eq!(errors(&body), vec![("age".to_string(), "must be a whole number".to_string())], "{}", age);
+        }
+    }
+
+    #[test]
+    fn each_field_reports_its_first_failed_rule() {
+        let expected = |name: &str, age: &str| vec![("name".to_string(), name.to_string()), ("age".to_string(), age.to_string())];
+        assert_eq!(errors("{}"), expected("is required", "is required"));
+        assert_eq!(errors(r#"{"name": "  ", "age": "-5"}"#), expected("can't be empty", "must be between 0 and 150"));
+        assert_eq!(errors(r#"{"name": "ana123", "age": 200}"#), expected("must have at most 5 characters", "must be between 0 and 150"));
+        assert_eq!(errors(r#"{"name": "a1", "age": 0}"#)[0].1, "must have only letters");
+    }
+}